@@ -0,0 +1,44 @@
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::TodoState;
+
+// One line of a todo's history; the log is only ever appended to
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Activity {
+    pub at: NaiveDateTime,
+    pub change: Change,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Change {
+    Created,
+    State(TodoState),
+    Renamed { from: String },
+    Moved { to: String },
+    Edited,
+    Archived,
+    Restored,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Created => write!(f, "created"),
+            Change::State(state) => write!(f, "marked {}", state.label().to_lowercase()),
+            Change::Renamed { from } => write!(f, "renamed from \"{}\"", from),
+            Change::Moved { to } => write!(f, "moved to {}", to),
+            Change::Edited => write!(f, "edited"),
+            Change::Archived => write!(f, "archived"),
+            Change::Restored => write!(f, "restored from the archive"),
+        }
+    }
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}  {}", self.at.format("%Y-%m-%d %H:%M"), self.change)
+    }
+}
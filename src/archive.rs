@@ -0,0 +1,88 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{TodoItem, TodoPage, TodoState, activity::Change, split_subtrees};
+
+// Longest auto-archive delay the setting accepts, about ten years
+pub const MAX_AUTO_ARCHIVE_DAYS: u32 = 3650;
+
+// A finished todo put away from its group, subtasks and all
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchivedTodo {
+    pub group: u32, // Id of the group it came from
+    pub group_title: String,
+    pub index: usize,
+    pub archived: NaiveDateTime,
+    pub item: TodoItem,
+    pub children: Vec<TodoItem>,
+}
+
+impl ArchivedTodo {
+    pub fn label(&self) -> String {
+        let done = self.item.completed.map_or("?".to_string(), |at| at.format("%Y-%m-%d").to_string());
+        match self.children.len() {
+            0 => format!("{} ({}, done {})", self.item.title, self.group_title, done),
+            n => format!("{} +{} subtask(s) ({}, done {})", self.item.title, n, self.group_title, done),
+        }
+    }
+
+    // Its old group, by id or else by title in case the group was recreated
+    fn destination(&self, page: &TodoPage) -> Option<usize> {
+        page.group_list.iter().position(|g| g.id == self.group)
+            .or_else(|| page.group_list.iter().position(|g| g.title == self.group_title))
+    }
+
+    pub fn can_restore(&self, page: &TodoPage) -> bool {
+        self.destination(page).is_some()
+    }
+
+    // Puts it back at its old position, pushed past any subtree that now sits there
+    pub fn restore(self, page: &mut TodoPage, now: NaiveDateTime) -> Option<()> {
+        let g = self.destination(page)?;
+        let g = &mut page.group_list[g];
+        let mut item = self.item;
+        item.log(now, Change::Restored);
+        let mut subtree = vec![item];
+        subtree.extend(self.children);
+        g.insert_subtree(self.index, subtree, 0);
+        Some(())
+    }
+}
+
+// Moves the group's done top level todos into the page archive. With `before`, only the
+// ones nobody touched since then. Returns how many went.
+pub fn archive_done(page: &mut TodoPage, group: usize, now: NaiveDateTime, before: Option<NaiveDateTime>) -> Option<usize> {
+    let g = page.group_list.get_mut(group)?;
+    let mut archived = Vec::new();
+    let mut kept = Vec::new();
+    for mut subtree in split_subtrees(g.clear_list()) {
+        let root = &subtree[0];
+        let untouched = before.is_none_or(|before| last_touched(root).is_some_and(|at| at < before));
+        if root.state != TodoState::Done || !untouched {
+            kept.extend(subtree);
+            continue;
+        }
+
+        let children = subtree.split_off(1);
+        let mut item = subtree.pop()?;
+        item.log(now, Change::Archived);
+        archived.push(ArchivedTodo {
+            group: g.id,
+            group_title: g.title.clone(),
+            index: kept.len(),
+            archived: now,
+            item,
+            children,
+        });
+    }
+    g.item_list = kept;
+
+    let count = archived.len();
+    page.archive.extend(archived);
+    Some(count)
+}
+
+// Restoring counts as touching it, so an old todo that was just restored stays out
+fn last_touched(item: &TodoItem) -> Option<NaiveDateTime> {
+    item.activity.last().map(|a| a.at).or(item.completed)
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+// When a todo is due; without a time it's due by the end of that day
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Due {
+    pub date: NaiveDate,
+    pub time: Option<NaiveTime>,
+}
+
+impl Due {
+    // Accepts "2025-06-01", "2025-06-01 14:30", "today", "tomorrow", "+3d", "+2w",
+    // each optionally followed by a time. Empty text clears the due date.
+    pub fn parse(text: &str, today: NaiveDate) -> Result<Option<Due>, String> {
+        let mut words = text.split_whitespace();
+        let Some(day) = words.next() else {
+            return Ok(None);
+        };
+
+        let date = match day.to_lowercase().as_str() {
+            "today" => today,
+            "tomorrow" => today + Days::new(1),
+            relative if relative.starts_with('+') => {
+                let rest = &relative[1..];
+                let count = rest.trim_end_matches(|c: char| c.is_alphabetic());
+                let unit = &rest[count.len()..];
+                let count: u64 = count.parse().map_err(|_| format!("Can't read \"{}\"", day))?;
+                let days = match unit {
+                    "d" => count,
+                    "w" => count.saturating_mul(7),
+                    _ => return Err(format!("Can't read \"{}\", use +Nd or +Nw", day)),
+                };
+                today.checked_add_days(Days::new(days)).ok_or("That's too far ahead".to_string())?
+            }
+            _ => NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                .map_err(|_| format!("Can't read \"{}\", use YYYY-MM-DD", day))?,
+        };
+
+        let time = match words.next() {
+            Some(time) => Some(NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("Can't read \"{}\", use HH:MM", time))?),
+            None => None,
+        };
+        if words.next().is_some() {
+            return Err("Too much text for a due date".to_string());
+        }
+
+        Ok(Some(Due { date, time }))
+    }
+
+    // The last moment the todo still counts as on time
+    pub fn deadline(&self) -> NaiveDateTime {
+        self.date.and_time(self.time.unwrap_or(NaiveTime::from_hms_opt(23, 59, 59).unwrap()))
+    }
+
+    pub fn is_past(&self, now: NaiveDateTime) -> bool {
+        now > self.deadline()
+    }
+}
+
+impl fmt::Display for Due {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.time {
+            Some(time) => write!(f, "{} {}", self.date.format("%Y-%m-%d"), time.format("%H:%M")),
+            None => write!(f, "{}", self.date.format("%Y-%m-%d")),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::NaiveDate;
+
+use crate::{Priority, TodoItem, TodoState, due::Due, recurrence::Recurrence};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditField {
+    Title,
+    Status,
+    Due,
+    Repeat,
+    Priority,
+    Description,
+    Save,
+    Discard,
+}
+
+impl EditField {
+    const ORDER: [EditField; 8] = [
+        EditField::Title,
+        EditField::Status,
+        EditField::Due,
+        EditField::Repeat,
+        EditField::Priority,
+        EditField::Description,
+        EditField::Save,
+        EditField::Discard,
+    ];
+
+    fn position(self) -> usize {
+        Self::ORDER.iter().position(|f| *f == self).unwrap()
+    }
+
+    pub fn next(self) -> Self {
+        Self::ORDER[(self.position() + 1) % Self::ORDER.len()]
+    }
+
+    pub fn prev(self) -> Self {
+        Self::ORDER[(self.position() + Self::ORDER.len() - 1) % Self::ORDER.len()]
+    }
+}
+
+pub enum EditTarget {
+    New { page: usize, group: usize },
+    Subtask { page: usize, group: usize, parent: usize },
+    Existing { page: usize, group: usize, todo: usize },
+}
+
+// Working copy of a todo while the form is open; nothing is committed until Save
+pub struct EditForm {
+    pub target: EditTarget,
+    pub item: TodoItem,
+    pub due_text: String, // Parsed into item.due on save
+    pub repeat_text: String, // Parsed into item.recurrence on save
+    pub focus: EditField,
+    pub error: Option<String>,
+}
+
+impl EditForm {
+    pub const PRIORITIES: [Option<Priority>; 4] = [None, Some(Priority::P1), Some(Priority::P2), Some(Priority::P3)];
+
+    pub fn create(page: usize, group: usize) -> Self {
+        Self {
+            target: EditTarget::New { page, group },
+            item: TodoItem::new(String::new()),
+            due_text: String::new(),
+            repeat_text: String::new(),
+            focus: EditField::Title,
+            error: None,
+        }
+    }
+
+    pub fn create_subtask(page: usize, group: usize, parent: usize) -> Self {
+        Self {
+            target: EditTarget::Subtask { page, group, parent },
+            ..Self::create(page, group)
+        }
+    }
+
+    pub fn edit(page: usize, group: usize, todo: usize, item: &TodoItem) -> Self {
+        Self {
+            target: EditTarget::Existing { page, group, todo },
+            item: item.clone(),
+            due_text: item.due.map_or(String::new(), |due| due.to_string()),
+            repeat_text: item.recurrence.as_ref().map_or(String::new(), |rule| rule.to_string()),
+            focus: EditField::Title,
+            error: None,
+        }
+    }
+
+    pub fn is_new(&self) -> bool {
+        !matches!(self.target, EditTarget::Existing { .. })
+    }
+
+    // Text buffer behind the focused field, if it takes typing
+    pub fn focused_text(&mut self) -> Option<&mut String> {
+        match self.focus {
+            EditField::Title => Some(&mut self.item.title),
+            EditField::Due => Some(&mut self.due_text),
+            EditField::Repeat => Some(&mut self.repeat_text),
+            EditField::Description => Some(&mut self.item.description),
+            _ => None,
+        }
+    }
+
+    pub fn cycle_status(&mut self, forward: bool) {
+        let states = TodoState::ALL;
+        let i = states.iter().position(|s| *s == self.item.state).unwrap_or(0);
+        let i = if forward { (i + 1) % states.len() } else { (i + states.len() - 1) % states.len() };
+        self.item.state = states[i];
+    }
+
+    pub fn cycle_priority(&mut self, forward: bool) {
+        let options = Self::PRIORITIES;
+        let i = options.iter().position(|p| *p == self.item.priority).unwrap_or(0);
+        let i = if forward { (i + 1) % options.len() } else { (i + options.len() - 1) % options.len() };
+        self.item.priority = options[i];
+    }
+
+    // Cleans up the fields; on failure focuses the offending one
+    pub fn validate(&mut self, today: NaiveDate) -> bool {
+        self.item.title = self.item.title.trim().to_string();
+        self.item.description = self.item.description.trim().to_string();
+        if self.item.state != TodoState::Failed {
+            self.item.failed_reason = None;
+        }
+
+        if self.item.title.is_empty() {
+            self.error = Some("Title can't be empty".to_string());
+            self.focus = EditField::Title;
+            return false;
+        }
+
+        match Due::parse(&self.due_text, today) {
+            Ok(due) => self.item.due = due,
+            Err(e) => {
+                self.error = Some(e);
+                self.focus = EditField::Due;
+                return false;
+            }
+        }
+
+        match Recurrence::parse(&self.repeat_text) {
+            Ok(rule) => self.item.recurrence = rule,
+            Err(e) => {
+                self.error = Some(e);
+                self.focus = EditField::Repeat;
+                return false;
+            }
+        }
+
+        self.error = None;
+        true
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeSet;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::{Priority, TodoItem, TodoState};
+
+// How far ahead "due soon" looks
+const DUE_SOON_DAYS: i64 = 3;
+
+// Narrows the page list down; every checked criterion has to hold, except that
+// checked priorities (and checked states) are one criterion that any of them meets
+#[derive(Clone, Default)]
+pub struct Filter {
+    pub overdue: bool,
+    pub due_soon: bool,
+    pub no_due: bool,
+    pub priorities: Vec<Priority>,
+    pub states: Vec<TodoState>,
+    pub tags: BTreeSet<String>,
+}
+
+// One checkbox of the filter overlay
+#[derive(Clone, PartialEq)]
+pub enum Criterion {
+    Overdue,
+    DueSoon,
+    NoDue,
+    Priority(Priority),
+    State(TodoState),
+    Tag(String),
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.labels().is_empty()
+    }
+
+    pub fn matches(&self, item: &TodoItem, now: NaiveDateTime) -> bool {
+        let open = !item.state.is_finished();
+        let soon = now + TimeDelta::days(DUE_SOON_DAYS);
+
+        (!self.overdue || (open && item.due.is_some_and(|d| d.is_past(now))))
+            && (!self.due_soon || (open && item.due.is_some_and(|d| !d.is_past(now) && d.deadline() <= soon)))
+            && (!self.no_due || item.due.is_none())
+            && (self.priorities.is_empty() || item.priority.is_some_and(|p| self.priorities.contains(&p)))
+            && (self.states.is_empty() || self.states.contains(&item.state))
+            && self.tags.is_subset(&item.tags)
+    }
+
+    pub fn is_checked(&self, criterion: &Criterion) -> bool {
+        match criterion {
+            Criterion::Overdue => self.overdue,
+            Criterion::DueSoon => self.due_soon,
+            Criterion::NoDue => self.no_due,
+            Criterion::Priority(priority) => self.priorities.contains(priority),
+            Criterion::State(state) => self.states.contains(state),
+            Criterion::Tag(tag) => self.tags.contains(tag),
+        }
+    }
+
+    pub fn toggle(&mut self, criterion: &Criterion) {
+        match criterion {
+            Criterion::Overdue => self.overdue = !self.overdue,
+            Criterion::DueSoon => self.due_soon = !self.due_soon,
+            Criterion::NoDue => self.no_due = !self.no_due,
+            Criterion::Priority(priority) => toggle_in(&mut self.priorities, *priority),
+            Criterion::State(state) => toggle_in(&mut self.states, *state),
+            Criterion::Tag(tag) => {
+                if !self.tags.remove(tag) {
+                    self.tags.insert(tag.clone());
+                }
+            }
+        }
+    }
+
+    // The checked criteria, for the header
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels = Vec::new();
+        let flags = [(self.overdue, "overdue"), (self.due_soon, "due soon"), (self.no_due, "no due")];
+        labels.extend(flags.iter().filter(|(on, _)| *on).map(|(_, label)| label.to_string()));
+        labels.extend(self.priorities.iter().map(|p| p.label().to_string()));
+        labels.extend(self.states.iter().map(|s| s.label().to_lowercase()));
+        labels.extend(self.tags.iter().map(|t| format!("#{}", t)));
+        labels
+    }
+}
+
+impl Criterion {
+    pub fn label(&self) -> String {
+        match self {
+            Criterion::Overdue => "Overdue".to_string(),
+            Criterion::DueSoon => format!("Due soon ({} days)", DUE_SOON_DAYS),
+            Criterion::NoDue => "No due date".to_string(),
+            Criterion::Priority(priority) => priority.label().to_string(),
+            Criterion::State(state) => state.label().to_string(),
+            Criterion::Tag(tag) => format!("#{}", tag),
+        }
+    }
+}
+
+fn toggle_in<T: PartialEq>(list: &mut Vec<T>, value: T) {
+    match list.iter().position(|v| *v == value) {
+        Some(i) => {
+            list.remove(i);
+        }
+        None => list.push(value),
+    }
+}
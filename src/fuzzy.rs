@@ -0,0 +1,25 @@
+// How well `query` matches `text` as a case-insensitive subsequence, higher is better;
+// None when it doesn't match. Runs of letters and letters starting a word count extra,
+// skipped letters cost a little. Spaces in the query are ignored.
+pub fn score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut from = 0;
+    let mut last: Option<usize> = None;
+
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = from + text[from..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        score += match last {
+            Some(last) if last + 1 == found => 5,
+            Some(last) => -((found - last - 1).min(5) as i32),
+            None => -(found.min(10) as i32),
+        };
+        last = Some(found);
+        from = found + 1;
+    }
+    Some(score)
+}
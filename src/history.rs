@@ -0,0 +1,49 @@
+use std::collections::VecDeque;
+
+use crate::TodoData;
+
+// How many steps back `u` can go
+const HISTORY_LIMIT: usize = 100;
+
+struct Step {
+    label: String,
+    data: TodoData,
+}
+
+// Undo/redo over whole copies of the data; a todo list is small enough that
+// this beats maintaining an inverse for every Mutation.
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Step>,
+    redo: Vec<Step>,
+}
+
+impl History {
+    // `before` is the data as it was prior to the change called `label`
+    pub fn record(&mut self, label: String, before: TodoData) {
+        self.redo.clear();
+        self.undo.push_back(Step { label, data: before });
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn undo(&mut self, data: &mut TodoData) -> Option<String> {
+        let step = self.undo.pop_back()?;
+        let current = std::mem::replace(data, step.data);
+        self.redo.push(Step { label: step.label.clone(), data: current });
+        Some(step.label)
+    }
+
+    pub fn redo(&mut self, data: &mut TodoData) -> Option<String> {
+        let step = self.redo.pop()?;
+        let current = std::mem::replace(data, step.data);
+        self.undo.push_back(Step { label: step.label.clone(), data: current });
+        Some(step.label)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
@@ -1,29 +1,76 @@
-use std::time::Duration;
-use crossterm::event::{self, Event, KeyCode, KeyEvent};
+mod activity;
+mod archive;
+mod due;
+mod filter;
+mod fuzzy;
+mod edit_form;
+mod history;
+mod mutation;
+mod query;
+mod recurrence;
+mod storage;
+mod trash;
+
+use std::{cmp::Ordering, collections::BTreeSet, panic::{self, AssertUnwindSafe}, time::{Duration, Instant}};
+use chrono::{Local, NaiveDateTime};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{DefaultTerminal, Frame,
     layout::{Constraint, Alignment, Layout, Direction, Rect},
-    widgets::{Block, List, ListItem, ListState, Borders, Paragraph},
+    widgets::{Block, Clear, List, ListItem, ListState, Borders, Paragraph, Wrap},
     text::{Line, Text, Span},
     style::{Style, Color, Modifier},
 };
 use color_eyre::Result;
-
+use serde::{Deserialize, Serialize};
+
+use activity::{Activity, Change};
+use archive::ArchivedTodo;
+use due::Due;
+use filter::{Criterion, Filter};
+use edit_form::{EditField, EditForm, EditTarget};
+use history::History;
+use mutation::Mutation;
+use query::{Query, SavedSearch};
+use recurrence::Recurrence;
+use storage::Storage;
+use trash::TrashEntry;
+
+#[derive(Clone, Copy, PartialEq)]
 enum TodoModes {
     PageSelect,
     Normal,
     Insert,
     Edit,
+    Detail,
+    Trash,
+    Archive,
+    MoveTo,
     Popup,
+    Confirm,
     AddSelect,
+    Filter,
+    BlockerPicker,
+    Search,
+    Finder,
 }
 
 enum ActiveInput {
     None,
     AddPage,
     AddGroup,
-    AddTodo,
+    RenamePage,
+    RenameGroup,
+    RenameTodo,
+    EditTags,
+    FailReason,
+    AutoArchiveDays,
+    AddSearch,
+    EditSearch,
+    RenameSearch,
+    Capture,
 }
 
+#[allow(dead_code)]
 enum AlertMode {
     None,
     Error,
@@ -31,23 +78,95 @@ enum AlertMode {
     Message,
 }
 
-#[derive(PartialEq)]
+enum ConfirmAction {
+    None,
+    RestoreRecovery(TodoData),
+    DeletePage(usize),
+    DeleteGroup(usize, usize),
+    DeleteTodo(usize, usize, usize),
+    ClearGroup(usize, usize),
+    PurgeTrash(usize),
+    EmptyTrash,
+}
+
+// A row of the page select list: built-in lists first, then real pages, then saved searches
+#[derive(Clone, Copy, PartialEq)]
+enum PageEntry {
+    Smart(SmartList),
+    Page(usize),
+    Search(usize),
+}
+
+// Built-in lists; all but the inbox are queries over every page
+#[derive(Clone, Copy, PartialEq)]
+enum SmartList {
+    Today,
+    Upcoming,
+    Overdue,
+    Inbox,
+}
+
+// What a finder result points at; group and todo are None for pages and groups
+#[derive(Clone, Copy)]
+struct FinderTarget {
+    page: usize,
+    group: Option<usize>,
+    todo: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 enum TodoState {
+    Doing,
     Done,
     Failed,
     Late,
     Pending,
 }
 
+// P1 is the most urgent
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Priority {
+    P1,
+    P2,
+    P3,
+}
+
+// How todos are ordered within their group; only the view, the saved order stays
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+enum SortOrder {
+    #[default]
+    Manual,
+    Priority,
+    Due,
+    Title,
+}
+
+// How a page lists its todos, chosen per page
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct ViewSettings {
+    #[serde(default)]
+    hide_finished: bool, // Done and failed todos, along with their subtasks
+    #[serde(default)]
+    sort: SortOrder,
+    #[serde(default)]
+    start_collapsed: bool, // Groups fold up whenever the page is opened
+    #[serde(default)]
+    inline_descriptions: bool,
+}
+
+// How often overdue todos are looked for while the app sits idle
+const DUE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 struct ApplicationState {
     mode: TodoModes,
     input_mode: ActiveInput,
     alert_mode: AlertMode,
+    confirm_action: ConfirmAction,
     title: String,
-    page_list: Vec<TodoPage>,
-    id_counter: u32,
+    data: TodoData,
     should_quit: bool,
     has_popup: bool,
+    popup_return: TodoModes,
 
     selected_page: Option <usize>,
     selected_group: Option<usize>,
@@ -55,30 +174,104 @@ struct ApplicationState {
 
     buffer_string: String,
     alert_string_buffer: String,
+    status_message: String,
+    edit_form: Option<EditForm>,
+    filter: Filter, // Only todos matching it are listed
+    detail_link: usize, // Highlighted blocker/dependent in the detail view
+    archive_page: Option<usize>, // Page whose archive is shown; None shows every page's
+    search: String, // Rows matching it stay highlighted until the page is left
+    search_origin: Option<usize>, // Row selected before searching, for Esc to go back to
+    finder: String,
+    finder_return: TodoModes, // Screen the finder was opened from
+    virtual_page: Option<SavedSearch>, // Shown instead of a page; selected_page follows the highlighted todo
+
+    // Storage
+    storage: Storage,
+    history: History,
+    due_checked: Option<Instant>, // None forces a check on the next loop
 
     // UI
     list_length: usize,
     display_infobar: bool,
     page_list_state: ListState,
     item_list_state: ListState,
+    trash_list_state: ListState,
+    archive_list_state: ListState,
+    move_list_state: ListState,
+    filter_list_state: ListState,
+    blocker_list_state: ListState,
+    finder_list_state: ListState,
+}
+
+// Everything that gets saved to disk
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct TodoData {
+    page_list: Vec<TodoPage>,
+    #[serde(default)]
+    trash: Vec<TrashEntry>,
+    #[serde(default)]
+    id_counter: u32, // Last id handed out; ids are unique across pages, groups and todos
+    #[serde(default)]
+    auto_archive_days: Option<u32>, // Done todos untouched this long get archived
+    #[serde(default)]
+    searches: Vec<SavedSearch>,
+    #[serde(default)]
+    inbox: Option<u32>, // Id of the page quick capture goes to, made on first use
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct TodoItem {
     id: u32,
     title: String,
     description: String,
     state: TodoState,
+    #[serde(default)]
+    due: Option<Due>,
+    #[serde(default)]
+    priority: Option<Priority>,
+    #[serde(default)]
+    tags: BTreeSet<String>,
+    #[serde(default)]
+    recurrence: Option<Recurrence>,
+    #[serde(default)]
+    depth: usize, // 0 for a top level todo, see TodoGroup::subtree_end
+    #[serde(default)]
+    folded: bool,
+    #[serde(default)]
+    auto_complete: bool, // Marks itself done once all its subtasks are
+    #[serde(default)]
+    blocked_by: BTreeSet<u32>, // Ids of todos, on any page, that have to be done first
+    #[serde(default)]
+    failed_reason: Option<String>,
+    #[serde(default)]
+    created: Option<NaiveDateTime>, // None for todos from before these were recorded
+    #[serde(default)]
+    started: Option<NaiveDateTime>, // First time it was marked Doing
+    #[serde(default)]
+    completed: Option<NaiveDateTime>, // Cleared again if it's reopened
+    #[serde(default)]
+    activity: Vec<Activity>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct TodoGroup {
+    #[serde(default)]
+    id: u32,
     show_items: bool,
     title: String,
     item_list: Vec<TodoItem>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct TodoPage {
+    #[serde(default)]
+    id: u32,
     title: String,
     group_list: Vec<TodoGroup>,
+    #[serde(default)]
+    archive: Vec<ArchivedTodo>,
+    #[serde(default)]
+    view: ViewSettings,
 }
 
 impl ApplicationState {
@@ -87,11 +280,12 @@ impl ApplicationState {
             mode: TodoModes::PageSelect,
             input_mode: ActiveInput::None,
             alert_mode: AlertMode::None,
+            confirm_action: ConfirmAction::None,
             title: title_,
-            page_list: Vec::new(),
-            id_counter: 1,
+            data: TodoData::default(),
             should_quit: false,
             has_popup: false,
+            popup_return: TodoModes::PageSelect,
 
             selected_page: None,
             selected_group: None,
@@ -99,56 +293,211 @@ impl ApplicationState {
 
             buffer_string: String::new(),
             alert_string_buffer: String::new(),
+            status_message: String::new(),
+            edit_form: None,
+            filter: Filter::default(),
+            detail_link: 0,
+            archive_page: None,
+            search: String::new(),
+            search_origin: None,
+            finder: String::new(),
+            finder_return: TodoModes::PageSelect,
+            virtual_page: None,
+
+            storage: Storage::new(storage::data_dir()),
+            history: History::default(),
+            due_checked: None,
 
             list_length: 0,
             display_infobar: false,
             page_list_state: ListState::default(),
             item_list_state: ListState::default(),
+            trash_list_state: ListState::default(),
+            archive_list_state: ListState::default(),
+            move_list_state: ListState::default(),
+            filter_list_state: ListState::default(),
+            blocker_list_state: ListState::default(),
+            finder_list_state: ListState::default(),
         } 
     }
 
-    fn add_page(&mut self, _title: String) {
-        self.page_list.push(TodoPage::new(_title))
+    fn load(&mut self) {
+        let mut warning = None;
+        match self.storage.load() {
+            Ok(loaded) => {
+                self.data = loaded.data;
+                self.page_list_state.select(Some(0));
+                self.clamp_page_selection();
+
+                if loaded.replayed > 0 || loaded.discarded > 0 {
+                    let mut message = format!(
+                        "The last session did not exit cleanly.\nRecovered {} unsaved change(s).",
+                        loaded.replayed);
+                    if loaded.discarded > 0 {
+                        message.push_str(&format!("\n{} damaged change(s) were dropped.", loaded.discarded));
+                    }
+                    warning = Some(message);
+                }
+                if let Some(e) = loaded.save_error {
+                    let message = format!("Could not save data: {}", e);
+                    warning = Some(match warning {
+                        Some(w) => format!("{}\n{}", w, message),
+                        None => message,
+                    });
+                }
+            }
+            Err(e) => {
+                let message = match self.storage.quarantine() {
+                    Ok(backup) => format!("Could not load data: {}\nMoved it to {}", e, backup.display()),
+                    Err(_) => format!("Could not load data: {}", e),
+                };
+                self.alert_box(AlertMode::Error, message);
+                return;
+            }
+        }
+
+        // Left behind by a panic in the previous session
+        match self.storage.read_recovery() {
+            Ok(Some(data)) => {
+                let unchanged = serde_json::to_value(&data).ok() == serde_json::to_value(&self.data).ok();
+                if unchanged {
+                    let _ = self.storage.clear_recovery();
+                }
+                else { // Supersedes the journal warning, it's the same crash
+                    self.confirm_box(ConfirmAction::RestoreRecovery(data),
+                        "todo_rs crashed last time and saved a copy\nof your data. Restore it?".to_string());
+                    return;
+                }
+            }
+            Ok(None) => (),
+            Err(e) => warning = Some(format!("Could not read recovery file: {}", e)),
+        }
+
+        if let Some(message) = warning {
+            self.alert_box(AlertMode::Warning, message);
+        }
+    }
+
+    fn restore_recovery(&mut self, data: TodoData) {
+        self.data = data;
+        self.data.assign_missing_ids();
+        self.history.clear();
+        self.page_list_state.select(Some(0));
+        self.clamp_page_selection();
+
+        let result = self.storage.snapshot(&self.data)
+            .and_then(|_| self.storage.clear_recovery());
+        if let Err(e) = result {
+            self.alert_box(AlertMode::Error, format!("Could not save restored data: {}", e));
+        }
     }
 
-    fn selected_page(&self) -> Option<&TodoPage> {
-        let p = self.selected_page?;
-        Some(self.page_list.get(p)?)
+    fn commit(&mut self, mutation: Mutation) { // Applies, journals and makes a change undoable
+        let label = mutation.describe(&self.data);
+        let before = self.data.clone();
+        let now = Local::now().naive_local();
+        if mutation.apply(&mut self.data, now).is_none() {
+            return;
+        }
+        self.history.record(label, before);
+        self.due_checked = None;
+
+        if let Err(e) = self.storage.record(mutation, now, &self.data) {
+            self.alert_box(AlertMode::Error, format!("Could not save data: {}", e));
+        }
     }
 
-    fn selected_group(&self) -> Option<&TodoGroup> {
-        let p = self.selected_page?;
-        let g = self.selected_group?;
+    fn check_due_dates(&mut self) {
+        self.due_checked = Some(Instant::now());
 
-        Some(self.page_list.get(p)?.group_list.get(g)?)
+        let now = Local::now().naive_local();
+        self.housekeep(Mutation::UpdateLate, now);
+        // Archiving shifts todos up, so wait until nothing is holding on to their positions
+        if !self.holds_positions() {
+            self.housekeep(Mutation::AutoArchive, now);
+        }
     }
 
-    fn selected_item(&self) -> Option<&TodoItem> {
-        let p = self.selected_page?;
-        let g = self.selected_group?;
-        let t = self.selected_todo?;
+    // An open form, prompt or picker that will act on a todo by its (page, group, todo) position
+    fn holds_positions(&self) -> bool {
+        let mode = if self.mode == TodoModes::Popup { self.popup_return } else { self.mode };
+        let prompt = matches!(self.input_mode, ActiveInput::RenameTodo | ActiveInput::RenameGroup
+            | ActiveInput::EditTags | ActiveInput::FailReason);
+        matches!(mode, TodoModes::Edit | TodoModes::Confirm | TodoModes::MoveTo | TodoModes::BlockerPicker | TodoModes::Detail)
+            || (mode == TodoModes::Insert && prompt)
+    }
+
+    // Journaled like any change, but kept out of undo: these happen on their own,
+    // and undoing one would only have it happen again
+    fn housekeep(&mut self, mutation: Mutation, now: NaiveDateTime) {
+        if mutation.apply(&mut self.data, now).is_none() {
+            return;
+        }
+        if let Err(e) = self.storage.record(mutation, now, &self.data) {
+            self.alert_box(AlertMode::Error, format!("Could not save data: {}", e));
+        }
+    }
+
+    fn undo(&mut self) {
+        let page = self.selected_page().map(|p| p.id);
+        match self.history.undo(&mut self.data) {
+            Some(label) => self.after_history_step(page, format!("Undid: {}", label)),
+            None => self.status_message = "Nothing to undo".to_string(),
+        }
+    }
+
+    fn redo(&mut self) {
+        let page = self.selected_page().map(|p| p.id);
+        match self.history.redo(&mut self.data) {
+            Some(label) => self.after_history_step(page, format!("Redid: {}", label)),
+            None => self.status_message = "Nothing to redo".to_string(),
+        }
+    }
+
+    // `page` is the id of the page that was open, found again by id since the step
+    // may have added, removed or reordered pages
+    fn after_history_step(&mut self, page: Option<u32>, message: String) {
+        self.status_message = message;
+        self.due_checked = None;
+
+        // The journal can't express "go back", so write the whole state out
+        if let Err(e) = self.storage.snapshot(&self.data) {
+            self.alert_box(AlertMode::Error, format!("Could not save data: {}", e));
+        }
 
-        Some(self.page_list.get(p)?.group_list.get(g)?.item_list.get(t)?)
+        if let Some(id) = page {
+            match self.data.page_list.iter().position(|p| p.id == id) {
+                Some(p) => self.select_page(p),
+                None => self.selected_page = None,
+            }
+        }
+        if self.selected_page().is_none() && self.virtual_page.is_none() {
+            self.mode = TodoModes::PageSelect;
+        }
+        if !matches!(self.mode, TodoModes::Normal | TodoModes::Detail | TodoModes::Popup) {
+            self.clamp_page_selection();
+        }
+        clamp_trash_selection(self);
     }
 
-    fn selected_mut_page(&mut self) -> Option<&mut TodoPage> {
+    fn selected_page(&self) -> Option<&TodoPage> {
         let p = self.selected_page?;
-        Some(self.page_list.get_mut(p)?)
+        self.data.page_list.get(p)
     }
 
-    fn selected_mut_group(&mut self) -> Option<&mut TodoGroup> {
+    fn selected_group(&self) -> Option<&TodoGroup> {
         let p = self.selected_page?;
         let g = self.selected_group?;
 
-        Some(self.page_list.get_mut(p)?.group_list.get_mut(g)?)
+        self.data.page_list.get(p)?.group_list.get(g)
     }
 
-    fn selected_mut_item(&mut self) -> Option<&mut TodoItem> {
+    fn selected_item(&self) -> Option<&TodoItem> {
         let p = self.selected_page?;
         let g = self.selected_group?;
         let t = self.selected_todo?;
 
-        Some(self.page_list.get_mut(p)?.group_list.get_mut(g)?.item_list.get_mut(t)?)
+        self.data.page_list.get(p)?.group_list.get(g)?.item_list.get(t)
     }
 
     fn selected_item_up(&mut self) {
@@ -161,6 +510,44 @@ impl ApplicationState {
         }
     }
 
+    // Rows of the current page whose group title, or todo title or description, contain the search
+    fn search_matches(&self) -> Vec<usize> {
+        let query = self.search.to_lowercase();
+        let Some(page) = self.selected_page() else {
+            return Vec::new();
+        };
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        self.page_rows().iter().enumerate().filter(|(_, (g, t))| {
+            let group = &page.group_list[*g];
+            match t {
+                None => group.title.to_lowercase().contains(&query),
+                Some(t) => {
+                    let item = &group.item_list[*t];
+                    item.title.to_lowercase().contains(&query) || item.description.to_lowercase().contains(&query)
+                }
+            }
+        }).map(|(row, _)| row).collect()
+    }
+
+    // Selects the next match after `from` (or before it, going back), wrapping around;
+    // `from` itself counts when `inclusive`
+    fn jump_to_match(&mut self, from: usize, forward: bool, inclusive: bool) -> bool {
+        let matches = self.search_matches();
+        let found = if forward {
+            matches.iter().find(|&&row| row > from || (inclusive && row == from)).or(matches.first())
+        }
+        else {
+            matches.iter().rev().find(|&&row| row < from || (inclusive && row == from)).or(matches.last())
+        };
+        if let Some(&row) = found {
+            self.item_list_state.select(Some(row));
+        }
+        found.is_some()
+    }
+
     fn selected_item_down(&mut self) {
         if self.list_length > 0 {
             let i = match self.item_list_state.selected() {
@@ -171,517 +558,3084 @@ impl ApplicationState {
         }
     }
 
-    fn alert_box(&mut self, a_mode: AlertMode, message_str: String) {
-        self.mode = TodoModes::Popup;
-        self.has_popup = true;
-        self.alert_mode = a_mode;
-        self.alert_string_buffer = message_str;
+    // Highlights a group (todo = None) or todo of the current page in the item list
+    fn select_row(&mut self, group: usize, todo: Option<usize>) {
+        let rows = self.page_rows();
+        // Falls back to the group header when the todo is folded or filtered away
+        let Some(row) = rows.iter().position(|r| *r == (group, todo))
+            .or_else(|| rows.iter().position(|r| *r == (group, None))) else {
+            return;
+        };
+        self.item_list_state.select(Some(row));
+        self.selected_group = Some(group);
+        self.selected_todo = todo;
     }
-}
 
-impl TodoPage {
-    fn new(_title: String) -> Self {
-        Self {
-            title: _title,
-            group_list: Vec::new(),
+    // Sets the highlighted todo's state, or toggles done/pending when `state` is None.
+    // Refuses with a warning to finish a todo that still waits on others.
+    fn change_state(&mut self, state: Option<TodoState>, reason: Option<String>) {
+        let (Some(page), Some(group), Some(todo), Some(item)) =
+            (self.selected_page, self.selected_group, self.selected_todo, self.selected_item()) else {
+            return;
+        };
+
+        let target = state.unwrap_or(item.toggled_state());
+        let blockers = self.data.open_blockers(item);
+        if target == TodoState::Done && item.state != TodoState::Done && !blockers.is_empty() {
+            let mut message = format!("\"{}\" is blocked by:", item.title);
+            for blocker in blockers.iter().take(3) {
+                message.push_str(&format!("\n- {}", blocker.title));
+            }
+            if blockers.len() > 3 {
+                message.push_str(&format!("\n…and {} more", blockers.len() - 3));
+            }
+            self.alert_box(AlertMode::Warning, message);
+            return;
         }
-    }
 
-    fn add_group(&mut self, _title: String) {
-        self.group_list.push(TodoGroup::new(_title));
-    }
-}
+        match state {
+            Some(state) => self.commit(Mutation::SetState { page, group, todo, state, reason }),
+            None => self.commit(Mutation::ToggleState { page, group, todo }),
+        }
 
-impl TodoGroup {
-    fn new(_title: String) -> Self {
-        Self {
-            show_items: true,
-            title: _title,
-            item_list: Vec::new(),
+        // A repeating todo leaves its next occurrence right below it
+        if let Some(group) = self.selected_group()
+            && let Some(next) = group.item_list.get(group.subtree_end(todo))
+            && group.item_list[todo].state == TodoState::Done
+            && next.recurrence.is_some()
+            && let Some(due) = next.due {
+            self.status_message = format!("Next \"{}\" is due {}", next.title, due);
         }
     }
 
-    fn add_todo(&mut self, _title: String) {
-        self.item_list.push(TodoItem::new(_title));
-    } 
-
-    fn toggle_show_items(&mut self) {
-        if self.show_items {
-            self.show_items = false;
+    // Opens the page a todo lives on, unfolds whatever hides it and highlights it
+    fn reveal(&mut self, page: usize, group: usize, todo: usize) {
+        self.virtual_page = None;
+        self.select_page(page);
+        let Some(g) = self.data.page_list.get(page).and_then(|p| p.group_list.get(group)) else {
+            return;
+        };
+
+        let mut folded = Vec::new();
+        let mut child = todo;
+        while let Some(parent) = g.parent_of(child) {
+            if g.item_list[parent].folded {
+                folded.push(parent);
+            }
+            child = parent;
         }
-        else {
-            self.show_items = true;
+        // Navigation, not an edit, so it stays out of undo
+        let now = Local::now().naive_local();
+        if !g.show_items {
+            self.housekeep(Mutation::ToggleShowItems { page, group }, now);
+        }
+        for parent in folded {
+            self.housekeep(Mutation::FoldTodo { page, group, todo: parent }, now);
+        }
+        if self.data.page_list[page].group_list[group].item_list.get(todo).is_some_and(|t| !self.item_visible(t)) {
+            self.filter = Filter::default();
         }
+        self.select_row(group, Some(todo));
     }
 
-    fn clear_list(&mut self) {
-        self.item_list.clear() 
+    // Ids the detail view can jump to: blockers first, then the todos waiting on this one
+    fn detail_links(&self) -> Vec<u32> {
+        let Some(item) = self.selected_item() else {
+            return Vec::new();
+        };
+        let mut links: Vec<u32> = item.blocked_by.iter().copied()
+            .filter(|id| self.data.todo_by_id(*id).is_some())
+            .collect();
+        links.extend(self.data.dependents(item.id).iter().map(|t| t.id));
+        links
     }
-    
-    fn rename(&mut self, _title: String) {
-        self.title = _title;
+
+    // Every other todo, labelled with its path, for the blocker picker
+    fn blocker_candidates(&self) -> Vec<(u32, String)> {
+        let selected = self.selected_item().map(|t| t.id);
+        self.data.todos()
+            .filter(|(.., item)| Some(item.id) != selected)
+            .map(|(p, g, _, item)| (item.id, self.data.todo_path(p, g, item)))
+            .collect()
     }
 
-    fn move_todo_up(&mut self) {
+    // Every page, group and todo matching the finder query, best first, with its path
+    fn finder_results(&self) -> Vec<(FinderTarget, String)> {
+        let mut results = Vec::new();
+        for (p, page) in self.data.page_list.iter().enumerate() {
+            results.push((FinderTarget { page: p, group: None, todo: None }, page.title.as_str(), page.title.clone()));
+            for (g, group) in page.group_list.iter().enumerate() {
+                let path = format!("{} › {}", page.title, group.title);
+                results.push((FinderTarget { page: p, group: Some(g), todo: None }, group.title.as_str(), path));
+                for (t, item) in group.item_list.iter().enumerate() {
+                    let path = self.data.todo_path(p, g, item);
+                    results.push((FinderTarget { page: p, group: Some(g), todo: Some(t) }, item.title.as_str(), path));
+                }
+            }
+        }
 
+        // A hit on the name itself beats one spread along the path
+        let mut scored: Vec<(i32, FinderTarget, String)> = results.into_iter()
+            .filter_map(|(target, name, path)| {
+                let score = fuzzy::score(&self.finder, name).map(|s| s + 20)
+                    .or_else(|| fuzzy::score(&self.finder, &path))?;
+                Some((score, target, path))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _, path)| (std::cmp::Reverse(*score), path.len()));
+        scored.into_iter().map(|(_, target, path)| (target, path)).collect()
     }
-    
-    fn move_todo_down(&mut self) {
 
+    fn open_finder(&mut self) {
+        self.finder.clear();
+        self.finder_return = self.mode;
+        self.finder_list_state.select(Some(0));
+        self.mode = TodoModes::Finder;
     }
-}
 
-impl TodoItem {
-    fn new(_title: String) -> Self {
-        Self {
-            id: 0,
-            title: _title,
-            description: String::new(),
-            state: TodoState::Pending,
-        }    
+    // Opens the target's page, unfolding whatever hides it
+    fn go_to(&mut self, target: FinderTarget) {
+        self.select_page(target.page);
+        self.open_page();
+        match (target.group, target.todo) {
+            (Some(group), Some(todo)) => self.reveal(target.page, group, todo),
+            (Some(group), None) => {
+                if self.selected_page().and_then(|p| p.group_list.get(group)).is_some_and(|g| !g.show_items) {
+                    self.housekeep(Mutation::ToggleShowItems { page: target.page, group }, Local::now().naive_local());
+                }
+                self.select_row(group, None);
+            }
+            _ => self.item_list_state.select(Some(0)),
+        }
     }
 
-    fn rename(&mut self, _title: String) {
-        self.title = _title;
+    // Re-highlights a todo of the group after it moved around within it
+    fn select_todo_by_id(&mut self, group: usize, id: Option<u32>) {
+        let todo = self.data.page_list.get(self.selected_page.unwrap_or(0))
+            .and_then(|p| p.group_list.get(group))
+            .and_then(|g| g.item_list.iter().position(|t| Some(t.id) == id));
+        if todo.is_some() {
+            self.select_row(group, todo);
+        }
     }
 
-    fn toggle_state(&mut self) {
-        if self.state != TodoState::Done {
-            self.state = TodoState::Done;
-        } 
-        else {
-            self.state = TodoState::Pending;
+    // Rows of the current page's list as (group, todo); todo is None for a group header
+    fn page_rows(&self) -> Vec<(usize, Option<usize>)> {
+        let mut rows = Vec::new();
+        let Some(page) = self.selected_page() else {
+            return rows;
+        };
+
+        for (group_index, group) in page.group_list.iter().enumerate() {
+            rows.push((group_index, None));
+            if !group.show_items {
+                continue;
+            }
+
+            let mut folded_at = None; // Depth of a folded or hidden todo whose subtasks are being skipped
+            for todo_index in group.display_order(page.view.sort) {
+                let todo = &group.item_list[todo_index];
+                match folded_at {
+                    Some(depth) if todo.depth > depth => continue,
+                    _ => folded_at = None,
+                }
+                if page.view.hide_finished && todo.state.is_finished() {
+                    folded_at = Some(todo.depth);
+                    continue;
+                }
+                if todo.folded {
+                    folded_at = Some(todo.depth);
+                }
+                if self.item_visible(todo) {
+                    rows.push((group_index, Some(todo_index)));
+                }
+            }
         }
+        rows
     }
-}
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
-    let terminal = ratatui::init();
-    let result = run(terminal);
-    ratatui::restore();
-    result
-}
+    fn item_visible(&self, item: &TodoItem) -> bool {
+        self.filter.is_empty() || self.filter.matches(item, Local::now().naive_local())
+    }
 
-fn run(mut terminal: DefaultTerminal) -> Result<()> {
-    let mut app_state: ApplicationState = ApplicationState::new("Balls-on-fire Todo(RUST)".to_string());
+    // Checkboxes of the filter overlay, tags last
+    fn filter_criteria(&self) -> Vec<Criterion> {
+        let mut criteria = vec![Criterion::Overdue, Criterion::DueSoon, Criterion::NoDue];
+        criteria.extend([Priority::P1, Priority::P2, Priority::P3].map(Criterion::Priority));
+        criteria.extend(TodoState::ALL.map(Criterion::State));
+        criteria.extend(self.all_tags().into_iter().map(Criterion::Tag));
+        criteria
+    }
 
-    loop {
-        // LOGIC 
+    // Every tag in use, for autocompletion and the filter
+    fn all_tags(&self) -> BTreeSet<String> {
+        self.data.page_list.iter()
+            .flat_map(|p| &p.group_list)
+            .flat_map(|g| &g.item_list)
+            .flat_map(|t| t.tags.iter().cloned())
+            .collect()
+    }
 
-        // RENDER 
-        let _ = terminal.draw(|f| render(f, &mut app_state));
+    // Tags offered for the word being typed in the tag popup
+    fn tag_suggestions(&self) -> Vec<String> {
+        let typed: Vec<&str> = self.buffer_string.split_whitespace().collect();
+        let partial = if self.buffer_string.ends_with(' ') { "" } else { typed.last().copied().unwrap_or("") };
+        let partial = normalize_tag(partial);
 
-        // INPUT
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                handle_input(key, &mut app_state);
-            }
-        }
+        self.all_tags().into_iter()
+            .filter(|tag| tag.starts_with(&partial) && !typed.iter().any(|t| normalize_tag(t) == *tag))
+            .collect()
+    }
 
-        if app_state.should_quit {
-            break;
+    // Every group a todo could be moved into, labelled "Page › Group"
+    fn move_destinations(&self) -> Vec<(usize, usize, String)> {
+        let mut destinations = Vec::new();
+        for (page_index, page) in self.data.page_list.iter().enumerate() {
+            for (group_index, group) in page.group_list.iter().enumerate() {
+                if Some(page_index) == self.selected_page && Some(group_index) == self.selected_group {
+                    continue;
+                }
+                destinations.push((page_index, group_index, format!("{} › {}", page.title, group.title)));
+            }
         }
+        destinations
     }
-    Ok(())
-}
 
-// --------------------------------- RENDER ---------------------------------
+    // (page, entry) of every archived todo in view, most recently archived first
+    fn archive_entries(&self) -> Vec<(usize, usize)> {
+        let mut entries: Vec<(usize, usize)> = self.data.page_list.iter().enumerate()
+            .filter(|(p, _)| self.archive_page.is_none_or(|page| page == *p))
+            .flat_map(|(p, page)| (0..page.archive.len()).map(move |e| (p, e)))
+            .collect();
+        entries.sort_by_key(|&(p, e)| std::cmp::Reverse(self.data.page_list[p].archive[e].archived));
+        entries
+    }
 
-fn render(frame: &mut Frame, app_state: &mut ApplicationState) { // Handles logic and Routes
-    match app_state.mode {
-        TodoModes::PageSelect => render_page_select(frame, app_state),
-        TodoModes::Normal => render_page(frame, app_state),
-        TodoModes::Insert => {
-            match app_state.input_mode {
-                ActiveInput::AddPage => render_popup_input_field(frame, app_state, "Create new Page:"),
-                ActiveInput::AddGroup =>  {
-                    render_page(frame, app_state);
-                    render_popup_input_field(frame, app_state, "Create new Group:");
-                },
-                ActiveInput::AddTodo => {
-                    render_page(frame, app_state);
-                    render_popup_input_field(frame, app_state, "Create new Todo:");
-                },
-                _ => (),
-            }
+    // Switches to the selected page's list, folding its groups if the page wants that
+    fn open_page(&mut self) {
+        if self.selected_page().is_none() {
+            return;
         }
-        TodoModes::AddSelect => {
-            render_page(frame, app_state);
-            render_add_select(frame, app_state);
+        self.virtual_page = None;
+        self.filter = Filter::default();
+        self.search.clear();
+        self.mode = TodoModes::Normal;
+        if let Some(page) = self.selected_page
+            && self.selected_page().is_some_and(|p| p.view.start_collapsed) {
+            self.housekeep(Mutation::CollapseGroups { page }, Local::now().naive_local());
+            self.select_row(0, None);
         }
-        _ => (),
     }
 
-    if app_state.has_popup {
-        render_alert_box(frame, &app_state.alert_mode, &app_state.alert_string_buffer.as_str());
+    // Applies a change to the selected page's view settings
+    fn change_view(&mut self, change: impl FnOnce(&mut ViewSettings) -> String) {
+        let (Some(page), Some(p)) = (self.selected_page, self.selected_page()) else {
+            return;
+        };
+        let mut view = p.view.clone();
+        let message = change(&mut view);
+        self.commit(Mutation::SetView { page, view });
+        self.status_message = message;
     }
-}
-
-fn render_page(frame: &mut Frame, app_state: &mut ApplicationState) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),
-            Constraint::Min(0),
-            Constraint::Length(5)])
-        .split(frame.area());
 
-    let header = chunks[0]; let body = chunks[1]; let footer = chunks[2];
- 
-    let title = format!("{} {}", "Page:", app_state.page_list[app_state.selected_page.unwrap()].title);
+    fn open_archive(&mut self, page: Option<usize>) {
+        self.archive_page = page;
+        let selected = if self.archive_entries().is_empty() { None } else { Some(0) };
+        self.archive_list_state.select(selected);
+        self.mode = TodoModes::Archive;
+    }
 
-    let header_block = Block::default().borders(Borders::ALL).title("Header");
-    let main_block = Block::default().borders(Borders::ALL).title(title);
-    let footer_block = Block::default().borders(Borders::ALL).title("Controls:");
+    // Keeps the page select highlight on an entry after pages come and go
+    fn clamp_page_selection(&mut self) {
+        let len = self.page_entries().len();
+        let selected = match self.page_list_state.selected() {
+            _ if len == 0 => None,
+            Some(i) => Some(i.min(len - 1)),
+            None => Some(0),
+        };
+        self.page_list_state.select(selected);
+        self.selected_page = match self.selected_entry() {
+            Some(PageEntry::Page(page)) => Some(page),
+            Some(PageEntry::Smart(SmartList::Inbox)) => self.data.inbox_index(),
+            _ => None,
+        };
+    }
 
-    frame.render_widget(header_block, header);
-    frame.render_widget(main_block.clone(), body);
-    frame.render_widget(footer_block, footer);
+    fn page_entries(&self) -> Vec<PageEntry> {
+        let inbox = self.data.inbox_index();
+        let smart = SmartList::ALL.into_iter().map(PageEntry::Smart);
+        let pages = (0..self.data.page_list.len()).filter(|p| Some(*p) != inbox).map(PageEntry::Page);
+        smart.chain(pages).chain((0..self.data.searches.len()).map(PageEntry::Search)).collect()
+    }
 
-    let inner_area = main_block.inner(body);
-    let inner1;
-    if app_state.display_infobar {
-        let main_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Percentage(60),
-                Constraint::Percentage(40),
-            ]).split(inner_area);
+    // Makes a page the current one and highlights it in the page select list
+    fn select_page(&mut self, page: usize) {
+        self.selected_page = Some(page);
+        let entry = if Some(page) == self.data.inbox_index() { PageEntry::Smart(SmartList::Inbox) } else { PageEntry::Page(page) };
+        self.page_list_state.select(self.page_entries().iter().position(|e| *e == entry));
+    }
 
-        inner1 = main_chunks[0]; 
-        let inner2 = main_chunks[1];
+    // How many todos a built-in list shows; for the inbox, the unfinished ones
+    fn smart_count(&self, list: SmartList) -> usize {
+        let Some(query) = list.query().and_then(|q| Query::parse(q).ok()) else {
+            let inbox = self.data.inbox_index().map(|p| &self.data.page_list[p]);
+            return inbox.map_or(0, |p| p.group_list.iter().flat_map(|g| &g.item_list)
+                .filter(|item| !item.state.is_finished()).count());
+        };
+        let now = Local::now().naive_local();
+        self.data.todos().filter(|(.., item)| query.matches(item, now)).count()
     }
-    else {
-        inner1 = inner_area;
+
+    // Quick capture: a title typed from anywhere lands in the inbox
+    fn start_capture(&mut self) {
+        self.popup_return = self.mode;
+        self.mode = TodoModes::Insert;
+        self.input_mode = ActiveInput::Capture;
     }
 
-    let mut list_state = app_state.item_list_state.clone();
-    let content_block = block_content_list(app_state);
+    // Opens a built-in list; the inbox page gets made the first time
+    fn open_smart(&mut self, list: SmartList) {
+        if let Some(query) = list.query() {
+            self.open_search(SavedSearch { title: list.title().to_string(), query: query.to_string() });
+            return;
+        }
+        if self.data.inbox_index().is_none() {
+            self.housekeep(Mutation::CreateInbox, Local::now().naive_local());
+        }
+        if let Some(inbox) = self.data.inbox_index() {
+            self.select_page(inbox);
+            self.open_page();
+        }
+    }
 
-    frame.render_stateful_widget(content_block, inner1, &mut list_state);
-}
+    fn selected_entry(&self) -> Option<PageEntry> {
+        self.page_list_state.selected().and_then(|i| self.page_entries().get(i).copied())
+    }
 
-fn block_content_list(app_state: &mut ApplicationState) -> List {
-    let block = Block::default().borders(Borders::ALL);
-    app_state.list_length = 0;
-    
-    let mut items:Vec <ListItem> = Vec::new();
-    let mut mapping: Vec<(usize, Option<usize>)> = Vec::new();
+    // Lists a query's results like a page
+    fn open_search(&mut self, search: SavedSearch) {
+        self.virtual_page = Some(search);
+        self.filter = Filter::default();
+        self.search.clear();
+        self.item_list_state.select(Some(0));
+        self.mode = TodoModes::Normal;
+    }
 
-    if let page = &app_state.page_list[app_state.selected_page.unwrap()] {
-        for (group_index, group) in page.group_list.iter().enumerate() {
-            let mut prefix_group = "";
-            if group.show_items { prefix_group = "▼"; }
-            else { prefix_group = "▶"; }
-
-            let group_string = format!("{} {}", prefix_group, group.title.clone());
-            items.push(ListItem::new(Line::from(
-                Span::styled(
-                    group_string, Style::default().add_modifier(Modifier::BOLD)))));
-            mapping.push((group_index, None));
-            if group.show_items {
-                for (todo_index, todo) in group.item_list.iter().enumerate() {
-                    // Todo string 
-                    let mut prefix_todo = "";
-                    match todo.state {
-                        TodoState::Done => prefix_todo = "[✔]",
-                        TodoState::Failed => prefix_todo = "[X]",
-                        _ => prefix_todo = "[ ]",
-                    }
+    // Every todo the shown saved search matches, as (page, group, todo)
+    fn virtual_rows(&self) -> Vec<(usize, usize, usize)> {
+        let Some(query) = self.virtual_page.as_ref().and_then(|s| Query::parse(&s.query).ok()) else {
+            return Vec::new();
+        };
+        let now = Local::now().naive_local();
+        self.data.todos().filter(|(.., item)| query.matches(item, now)).map(|(p, g, t, _)| (p, g, t)).collect()
+    }
 
-                    let todo_string = format!("    {} {}", prefix_todo, todo.title.as_str());
-                    items.push(ListItem::new(Line::from(todo_string)));
-                    mapping.push((group_index, Some(todo_index)));
-                }
-            }
+    fn base_mode(&self) -> TodoModes { // Screen underneath the current popup/overlay
+        match self.mode {
+            TodoModes::Popup | TodoModes::Confirm => self.popup_return,
+            TodoModes::PageSelect => TodoModes::PageSelect,
+            TodoModes::Trash => TodoModes::Trash,
+            TodoModes::Archive => TodoModes::Archive,
+            TodoModes::BlockerPicker => TodoModes::BlockerPicker,
+            TodoModes::Finder => self.finder_return,
+            TodoModes::Insert if matches!(self.input_mode, ActiveInput::AddPage | ActiveInput::RenamePage
+                | ActiveInput::AddSearch | ActiveInput::EditSearch | ActiveInput::RenameSearch) => TodoModes::PageSelect,
+            TodoModes::Insert if matches!(self.input_mode, ActiveInput::AutoArchiveDays) => TodoModes::Archive,
+            TodoModes::Insert if matches!(self.input_mode, ActiveInput::Capture) => self.popup_return,
+            _ => TodoModes::Normal,
         }
     }
 
+    fn alert_box(&mut self, a_mode: AlertMode, message_str: String) {
+        self.popup_return = self.base_mode();
+        self.mode = TodoModes::Popup;
+        self.has_popup = true;
+        self.alert_mode = a_mode;
+        self.alert_string_buffer = message_str;
+    }
 
-    app_state.list_length = mapping.len();
-
-    if mapping.is_empty() { // None selected
-        app_state.item_list_state.select(None);
-        app_state.selected_group = None;
-        app_state.selected_todo = None;
-    } else {
-        let selected = app_state.item_list_state.selected().unwrap_or(0);
-        let selected = selected.min(mapping.len() - 1);
-        app_state.item_list_state.select(Some(selected));
+    fn confirm_box(&mut self, action: ConfirmAction, message_str: String) {
+        self.popup_return = self.base_mode();
+        self.mode = TodoModes::Confirm;
+        self.confirm_action = action;
+        self.alert_string_buffer = message_str;
+    }
+}
 
-        if let Some((group_index, todo_index)) = mapping.get(selected) {
-            app_state.selected_group = Some(*group_index);
-            app_state.selected_todo = *todo_index;
+impl TodoData {
+    fn inbox_index(&self) -> Option<usize> {
+        let id = self.inbox?;
+        self.page_list.iter().position(|p| p.id == id)
+    }
+
+    // Every todo along with where it lives, in display order
+    fn todos(&self) -> impl Iterator<Item = (usize, usize, usize, &TodoItem)> {
+        self.page_list.iter().enumerate().flat_map(|(p, page)| {
+            page.group_list.iter().enumerate().flat_map(move |(g, group)| {
+                group.item_list.iter().enumerate().map(move |(t, item)| (p, g, t, item))
+            })
+        })
+    }
+
+    fn locate(&self, id: u32) -> Option<(usize, usize, usize)> {
+        self.todos().find(|(.., item)| item.id == id).map(|(p, g, t, _)| (p, g, t))
+    }
+
+    fn todo_by_id(&self, id: u32) -> Option<&TodoItem> {
+        self.todos().find(|(.., item)| item.id == id).map(|(.., item)| item)
+    }
+
+    // Blockers that still exist and aren't done; deleted ones no longer hold anything up
+    fn open_blockers(&self, item: &TodoItem) -> Vec<&TodoItem> {
+        item.blocked_by.iter()
+            .filter_map(|id| self.todo_by_id(*id))
+            .filter(|blocker| blocker.state != TodoState::Done)
+            .collect()
+    }
+
+    fn dependents(&self, id: u32) -> Vec<&TodoItem> {
+        self.todos().map(|(.., item)| item).filter(|item| item.blocked_by.contains(&id)).collect()
+    }
+
+    // Whether `id` has to wait for `other`, directly or through its blockers
+    fn waits_on(&self, id: u32, other: u32) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![id];
+        while let Some(next) = stack.pop() {
+            if next == other {
+                return true;
+            }
+            if seen.insert(next) && let Some(item) = self.todo_by_id(next) {
+                stack.extend(item.blocked_by.iter().copied());
+            }
         }
+        false
     }
 
-    List::new(items).block(block)
-        .highlight_style(
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Green)
-        )
+    // "Page › Group › Todo", for pickers that span pages
+    fn todo_path(&self, page: usize, group: usize, item: &TodoItem) -> String {
+        let page = &self.page_list[page];
+        format!("{} › {} › {}", page.title, page.group_list[group].title, item.title)
+    }
+
+    fn for_each_id(&mut self, mut f: impl FnMut(&mut u32)) {
+        let visit_group = |group: &mut TodoGroup, f: &mut dyn FnMut(&mut u32)| {
+            f(&mut group.id);
+            group.item_list.iter_mut().for_each(|item| f(&mut item.id));
+        };
+
+        let visit_page = |page: &mut TodoPage, f: &mut dyn FnMut(&mut u32)| {
+            f(&mut page.id);
+            page.group_list.iter_mut().for_each(|group| visit_group(group, f));
+            for archived in &mut page.archive {
+                f(&mut archived.item.id);
+                archived.children.iter_mut().for_each(|child| f(&mut child.id));
+            }
+        };
+
+        for page in &mut self.page_list {
+            visit_page(page, &mut f);
+        }
+        for entry in &mut self.trash {
+            match entry {
+                TrashEntry::Page { page, .. } => visit_page(page, &mut f),
+                TrashEntry::Group { group, .. } => visit_group(group, &mut f),
+                TrashEntry::Todo { item, children, .. } => {
+                    f(&mut item.id);
+                    children.iter_mut().for_each(|child| f(&mut child.id));
+                }
+            }
+        }
+    }
+
+    // Data saved before ids existed has them all at 0; returns whether anything changed
+    fn assign_missing_ids(&mut self) -> bool {
+        let mut highest = self.id_counter;
+        let mut missing = false;
+        self.for_each_id(|id| {
+            highest = highest.max(*id);
+            missing |= *id == 0;
+        });
+        self.id_counter = highest;
+
+        if missing {
+            let mut next = highest;
+            self.for_each_id(|id| if *id == 0 {
+                next += 1;
+                *id = next;
+            });
+            self.id_counter = next;
+        }
+        missing
+    }
 }
 
-fn render_add_select(frame: &mut Frame, app_state: &mut ApplicationState) {
-    let w = 30;
-    let h = 4;
-    let rect = Rect::new((frame.area().width - w) / 2,
-        (frame.area().height - h) / 2,
-        w, h );
+// "#Work" and "work" are the same tag
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
 
-    let text = Paragraph::new(Text::from(vec![
-            Line::from("(i) Add Item"),
-            Line::from("(g) Add Group"),
-    ])).block(Block::default().borders(Borders::ALL).title("Select"));
+impl TodoPage {
+    fn new(_title: String) -> Self {
+        Self {
+            id: 0,
+            title: _title,
+            group_list: Vec::new(),
+            archive: Vec::new(),
+            view: ViewSettings::default(),
+        }
+    }
 
-    frame.render_widget(text, rect);
+    fn add_group(&mut self, group: TodoGroup) {
+        self.group_list.push(group);
+    }
+
+    fn rename(&mut self, _title: String) {
+        self.title = _title;
+    }
+
+    fn move_group_up(&mut self, index: usize) -> Option<()> {
+        if index == 0 || index >= self.group_list.len() {
+            return None;
+        }
+        self.group_list.swap(index, index - 1);
+        Some(())
+    }
+
+    fn move_group_down(&mut self, index: usize) -> Option<()> {
+        if index + 1 >= self.group_list.len() {
+            return None;
+        }
+        self.group_list.swap(index, index + 1);
+        Some(())
+    }
+
+    fn remove_group(&mut self, index: usize) -> Option<TodoGroup> {
+        if index >= self.group_list.len() {
+            return None;
+        }
+        Some(self.group_list.remove(index))
+    }
 }
 
-fn render_page_select(frame: &mut Frame, app_state: &mut ApplicationState) {
-    let area = frame.area().centered(
-        Constraint::Length(50),
-        Constraint::Length(25)
-    );
+impl TodoGroup {
+    fn new(_title: String) -> Self {
+        Self {
+            id: 0,
+            show_items: true,
+            title: _title,
+            item_list: Vec::new(),
+        }
+    }
+
+    fn add_todo(&mut self, item: TodoItem) {
+        self.item_list.push(item);
+    } 
+
+    // Todos are kept flat in display order; `depth` makes each one a subtask of the
+    // nearest shallower todo above it, so a todo's subtree is the run of deeper ones after it
+    fn subtree_end(&self, index: usize) -> usize {
+        let depth = self.item_list[index].depth;
+        self.item_list[index + 1..].iter()
+            .position(|t| t.depth <= depth)
+            .map_or(self.item_list.len(), |i| index + 1 + i)
+    }
+
+    fn parent_of(&self, index: usize) -> Option<usize> {
+        let depth = self.item_list.get(index)?.depth;
+        self.item_list[..index].iter().rposition(|t| t.depth < depth)
+    }
+
+    fn children_of(&self, index: usize) -> impl Iterator<Item = &TodoItem> {
+        let depth = self.item_list[index].depth;
+        self.item_list[index + 1..self.subtree_end(index)].iter().filter(move |t| t.depth == depth + 1)
+    }
+
+    // (done, total) over the direct subtasks
+    fn progress(&self, index: usize) -> (usize, usize) {
+        self.children_of(index).fold((0, 0), |(done, total), t| (done + (t.state == TodoState::Done) as usize, total + 1))
+    }
+
+    // Start of the sibling just above, None if the todo is the first under its parent
+    fn previous_sibling(&self, index: usize) -> Option<usize> {
+        let depth = self.item_list.get(index)?.depth;
+        let previous = self.item_list[..index].iter().rposition(|t| t.depth <= depth)?;
+        (self.item_list[previous].depth == depth).then_some(previous)
+    }
+
+    fn add_subtask(&mut self, parent: usize, mut item: TodoItem) -> Option<()> {
+        self.item_list.get(parent)?;
+        let end = self.subtree_end(parent);
+        let p = &mut self.item_list[parent];
+        p.folded = false;
+        item.depth = p.depth + 1;
+        self.item_list.insert(end, item);
+        Some(())
+    }
+
+    fn remove_subtree(&mut self, index: usize) -> Option<Vec<TodoItem>> {
+        if index >= self.item_list.len() {
+            return None;
+        }
+        let end = self.subtree_end(index);
+        Some(self.item_list.drain(index..end).collect())
+    }
+
+    // Puts a subtree back with its root at `depth`, or as deep as fits at `index`; pushed
+    // past anything deeper sitting there, so it doesn't adopt someone else's subtasks
+    fn insert_subtree(&mut self, index: usize, mut subtree: Vec<TodoItem>, depth: usize) {
+        let Some(root) = subtree.first() else {
+            return;
+        };
+        let len = self.item_list.len();
+        let index = index.min(len);
+        let fits = index.checked_sub(1).map_or(0, |i| self.item_list[i].depth + 1);
+        let root_depth = root.depth;
+        let depth = depth.min(fits);
+        let index = (index..len).find(|&i| self.item_list[i].depth <= depth).unwrap_or(len);
+
+        for item in &mut subtree {
+            item.depth = item.depth - root_depth + depth;
+        }
+        self.item_list.splice(index..index, subtree);
+    }
+
+    fn indent(&mut self, index: usize) -> Option<()> {
+        let sibling = self.previous_sibling(index)?;
+        self.item_list[sibling].folded = false;
+        let end = self.subtree_end(index);
+        self.item_list[index..end].iter_mut().for_each(|t| t.depth += 1);
+        Some(())
+    }
+
+    // Becomes the next sibling of its parent, rather than adopting the todos below it
+    fn outdent(&mut self, index: usize) -> Option<()> {
+        let parent = self.parent_of(index)?;
+        let end = self.subtree_end(index);
+        let parent_end = self.subtree_end(parent);
+        let len = end - index;
+
+        self.item_list[index..parent_end].rotate_left(len);
+        self.item_list[parent_end - len..parent_end].iter_mut().for_each(|t| t.depth -= 1);
+        Some(())
+    }
+
+    fn toggle_fold(&mut self, index: usize) -> Option<()> {
+        let item = self.item_list.get_mut(index)?;
+        item.folded = !item.folded;
+        Some(())
+    }
+
+    // Walks up from a freshly completed todo, finishing parents that asked for it,
+    // unless a blocker of theirs is still open
+    fn complete_parents(&mut self, index: usize, now: NaiveDateTime, unfinished: &BTreeSet<u32>) {
+        let mut finished = vec![self.item_list[index].id];
+        let mut child = index;
+        while let Some(parent) = self.parent_of(child) {
+            let (done, total) = self.progress(parent);
+            let p = &mut self.item_list[parent];
+            let blocked = p.blocked_by.iter().any(|b| unfinished.contains(b) && !finished.contains(b));
+            if !p.auto_complete || p.state == TodoState::Done || done < total || blocked {
+                break;
+            }
+            p.mark(TodoState::Done, now);
+            finished.push(p.id);
+            child = parent;
+        }
+    }
+
+    fn toggle_show_items(&mut self) {
+        self.show_items = !self.show_items;
+    }
+
+    fn clear_list(&mut self) -> Vec<TodoItem> { // Hands the items back for the trash
+        std::mem::take(&mut self.item_list)
+    }
+    
+    fn rename(&mut self, _title: String) {
+        self.title = _title;
+    }
+
+    // Todo indices in the order `sort` lists them; subtasks stay under their parent
+    fn display_order(&self, sort: SortOrder) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.item_list.len());
+        self.order_siblings(0, self.item_list.len(), sort, &mut order);
+        order
+    }
+
+    fn order_siblings(&self, start: usize, end: usize, sort: SortOrder, order: &mut Vec<usize>) {
+        let mut roots = Vec::new();
+        let mut index = start;
+        while index < end {
+            roots.push(index);
+            index = self.subtree_end(index);
+        }
+        roots.sort_by(|a, b| sort.compare(&self.item_list[*a], &self.item_list[*b]));
+
+        for root in roots {
+            order.push(root);
+            self.order_siblings(root + 1, self.subtree_end(root), sort, order);
+        }
+    }
+
+    fn sort_by_priority(&mut self) { // Stable, and subtasks are sorted among their siblings
+        self.item_list = sort_siblings(std::mem::take(&mut self.item_list));
+    }
+
+    // Moves are by whole subtree, and only past siblings
+    fn move_todo_up(&mut self, index: usize) -> Option<()> {
+        let sibling = self.previous_sibling(index)?;
+        let end = self.subtree_end(index);
+        self.item_list[sibling..end].rotate_left(index - sibling);
+        Some(())
+    }
+    
+    fn move_todo_down(&mut self, index: usize) -> Option<()> {
+        let depth = self.item_list.get(index)?.depth;
+        let sibling = self.subtree_end(index);
+        if self.item_list.get(sibling)?.depth != depth {
+            return None;
+        }
+        let end = self.subtree_end(sibling);
+        self.item_list[index..end].rotate_left(sibling - index);
+        Some(())
+    }
+}
+
+// Cuts a run of todos into subtrees, each starting at the shallowest depth
+fn split_subtrees(items: Vec<TodoItem>) -> Vec<Vec<TodoItem>> {
+    let Some(depth) = items.first().map(|t| t.depth) else {
+        return Vec::new();
+    };
+
+    let mut subtrees: Vec<Vec<TodoItem>> = Vec::new();
+    for item in items {
+        match subtrees.last_mut() {
+            Some(subtree) if item.depth > depth => subtree.push(item),
+            _ => subtrees.push(vec![item]),
+        }
+    }
+    subtrees
+}
+
+fn sort_siblings(items: Vec<TodoItem>) -> Vec<TodoItem> {
+    let mut subtrees = split_subtrees(items);
+    subtrees.sort_by_key(|subtree| Priority::rank(subtree[0].priority));
+
+    subtrees.into_iter().flat_map(|mut subtree| {
+        let children = subtree.split_off(1);
+        subtree.extend(sort_siblings(children));
+        subtree
+    }).collect()
+}
+
+impl TodoState {
+    const ALL: [TodoState; 5] = [
+        TodoState::Pending,
+        TodoState::Doing,
+        TodoState::Done,
+        TodoState::Failed,
+        TodoState::Late,
+    ];
+
+    fn is_finished(&self) -> bool {
+        matches!(self, TodoState::Done | TodoState::Failed)
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            TodoState::Pending => "Pending",
+            TodoState::Doing => "Doing",
+            TodoState::Done => "Done",
+            TodoState::Failed => "Failed",
+            TodoState::Late => "Late",
+        }
+    }
+
+    fn marker(&self) -> &'static str {
+        match self {
+            TodoState::Pending => "[ ]",
+            TodoState::Doing => "[◐]",
+            TodoState::Done => "[✔]",
+            TodoState::Failed => "[✘]",
+            TodoState::Late => "[!]",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            TodoState::Pending => Color::Reset,
+            TodoState::Doing => Color::Yellow,
+            TodoState::Done => Color::Green,
+            TodoState::Failed | TodoState::Late => Color::Red,
+        }
+    }
+}
+
+impl Priority {
+    fn label(&self) -> &'static str {
+        match self {
+            Priority::P1 => "P1",
+            Priority::P2 => "P2",
+            Priority::P3 => "P3",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Priority::P1 => Color::Red,
+            Priority::P2 => Color::Yellow,
+            Priority::P3 => Color::Blue,
+        }
+    }
+
+    // No priority sorts after P3
+    fn rank(priority: Option<Priority>) -> u8 {
+        match priority {
+            Some(Priority::P1) => 0,
+            Some(Priority::P2) => 1,
+            Some(Priority::P3) => 2,
+            None => 3,
+        }
+    }
+
+    fn raise(priority: Option<Priority>) -> Option<Priority> {
+        match priority {
+            None => Some(Priority::P3),
+            Some(Priority::P3) => Some(Priority::P2),
+            Some(_) => Some(Priority::P1),
+        }
+    }
+
+    fn lower(priority: Option<Priority>) -> Option<Priority> {
+        match priority {
+            Some(Priority::P1) => Some(Priority::P2),
+            Some(Priority::P2) => Some(Priority::P3),
+            _ => None,
+        }
+    }
+}
+
+impl SortOrder {
+    const ALL: [SortOrder; 4] = [SortOrder::Manual, SortOrder::Priority, SortOrder::Due, SortOrder::Title];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SortOrder::Manual => "manual order",
+            SortOrder::Priority => "priority",
+            SortOrder::Due => "due date",
+            SortOrder::Title => "title",
+        }
+    }
+
+    fn next(self) -> SortOrder {
+        let i = SortOrder::ALL.iter().position(|s| *s == self).unwrap_or(0);
+        SortOrder::ALL[(i + 1) % SortOrder::ALL.len()]
+    }
+
+    // Ties keep the manual order, since sorting is stable
+    fn compare(&self, a: &TodoItem, b: &TodoItem) -> Ordering {
+        let due = |t: &TodoItem| (t.due.is_none(), t.due.map(|d| d.deadline())); // Undated ones last
+        match self {
+            SortOrder::Manual => Ordering::Equal,
+            SortOrder::Priority => Priority::rank(a.priority).cmp(&Priority::rank(b.priority)),
+            SortOrder::Due => due(a).cmp(&due(b)),
+            SortOrder::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+        }
+    }
+}
+
+impl SmartList {
+    const ALL: [SmartList; 4] = [SmartList::Today, SmartList::Upcoming, SmartList::Overdue, SmartList::Inbox];
+
+    fn title(&self) -> &'static str {
+        match self {
+            SmartList::Today => "Today",
+            SmartList::Upcoming => "Upcoming",
+            SmartList::Overdue => "Overdue",
+            SmartList::Inbox => "Inbox",
+        }
+    }
+
+    fn query(&self) -> Option<&'static str> {
+        match self {
+            SmartList::Today => Some("due:today -state:done,failed"),
+            SmartList::Upcoming => Some("due>0d due<7d -state:done,failed"),
+            SmartList::Overdue => Some("due:overdue"),
+            SmartList::Inbox => None,
+        }
+    }
+}
+
+impl TodoItem {
+    fn new(_title: String) -> Self {
+        Self {
+            id: 0,
+            title: _title,
+            description: String::new(),
+            state: TodoState::Pending,
+            due: None,
+            priority: None,
+            tags: BTreeSet::new(),
+            recurrence: None,
+            depth: 0,
+            folded: false,
+            auto_complete: false,
+            blocked_by: BTreeSet::new(),
+            failed_reason: None,
+            created: None,
+            started: None,
+            completed: None,
+            activity: Vec::new(),
+        }    
+    }
+
+    fn rename(&mut self, _title: String, now: NaiveDateTime) {
+        if _title != self.title {
+            let from = std::mem::replace(&mut self.title, _title);
+            self.log(now, Change::Renamed { from });
+        }
+    }
+
+    fn log(&mut self, now: NaiveDateTime, change: Change) {
+        self.activity.push(Activity { at: now, change });
+    }
+
+    // Every state change goes through here so it gets timestamped and logged
+    fn mark(&mut self, state: TodoState, now: NaiveDateTime) {
+        if state == self.state {
+            return;
+        }
+        self.state = state;
+        if state == TodoState::Doing && self.started.is_none() {
+            self.started = Some(now);
+        }
+        self.completed = (state == TodoState::Done).then_some(now);
+        self.log(now, Change::State(state));
+    }
+
+    // Pending todos past their due date turn Late, and back once it's pushed out;
+    // returns whether the state changed
+    fn update_late(&mut self, now: NaiveDateTime) -> bool {
+        let Some(due) = self.due else {
+            return false;
+        };
+
+        let state = match self.state {
+            TodoState::Pending if due.is_past(now) => TodoState::Late,
+            TodoState::Late if !due.is_past(now) => TodoState::Pending,
+            _ => return false,
+        };
+        self.mark(state, now);
+        true
+    }
+
+    // Hands the repeat rule over to a fresh copy due next time; the done one stays as a record
+    fn take_next_occurrence(&mut self, now: NaiveDateTime) -> Option<TodoItem> {
+        let rule = self.recurrence.take()?;
+        let mut next = self.clone();
+        next.state = TodoState::Pending;
+        next.folded = false;
+        next.due = Some(rule.next_due(self.due, now.date())?);
+        next.recurrence = Some(rule.pinned(self.due));
+        next.created = Some(now);
+        next.started = None;
+        next.completed = None;
+        next.activity = vec![Activity { at: now, change: Change::Created }];
+        Some(next)
+    }
+
+    fn toggled_state(&self) -> TodoState {
+        if self.state != TodoState::Done {
+            TodoState::Done
+        } 
+        else {
+            TodoState::Pending
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let terminal = ratatui::init(); // Also hooks panics to restore the terminal first
+    let result = run(terminal);
+    ratatui::restore();
+    result
+}
+
+fn run(mut terminal: DefaultTerminal) -> Result<()> {
+    let mut app_state: ApplicationState = ApplicationState::new("Balls-on-fire Todo(RUST)".to_string());
+    app_state.load();
+
+    match panic::catch_unwind(AssertUnwindSafe(|| event_loop(&mut terminal, &mut app_state))) {
+        Ok(result) => {
+            result?;
+            app_state.storage.snapshot(&app_state.data)
+        }
+        Err(payload) => { // Terminal is restored by now, dump the data before going down
+            match app_state.storage.write_recovery(&app_state.data) {
+                Ok(path) => eprintln!("Your data was saved to {}", path.display()),
+                Err(e) => eprintln!("Could not save your data: {}", e),
+            }
+            panic::resume_unwind(payload)
+        }
+    }
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app_state: &mut ApplicationState) -> Result<()> {
+    loop {
+        // LOGIC 
+        if app_state.due_checked.is_none_or(|checked| checked.elapsed() >= DUE_CHECK_INTERVAL) {
+            app_state.check_due_dates();
+        }
+
+        // RENDER 
+        terminal.draw(|f| render(f, app_state))?;
+
+        // INPUT
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()? {
+            handle_input(key, app_state);
+        }
+
+        if app_state.should_quit {
+            return Ok(());
+        }
+    }
+}
+
+// --------------------------------- RENDER ---------------------------------
+
+fn render(frame: &mut Frame, app_state: &mut ApplicationState) { // Handles logic and Routes
+    match app_state.mode {
+        TodoModes::PageSelect => render_page_select(frame, app_state),
+        TodoModes::Normal | TodoModes::Search => render_page(frame, app_state),
+        TodoModes::Insert => {
+            match app_state.input_mode {
+                ActiveInput::AddPage => render_popup_input_field(frame, app_state, "Create new Page:"),
+                ActiveInput::AddGroup =>  {
+                    render_page(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Create new Group:");
+                },
+                ActiveInput::RenamePage => {
+                    render_page_select(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Rename Page:");
+                }
+                ActiveInput::RenameGroup => {
+                    render_page(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Rename Group:");
+                }
+                ActiveInput::RenameTodo => {
+                    render_page(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Rename Todo:");
+                }
+                ActiveInput::FailReason => {
+                    render_page(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Why did it fail? (optional)");
+                }
+                ActiveInput::EditTags => {
+                    render_page(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Tags (space separated, [Tab] complete):");
+                    render_tag_suggestions(frame, app_state);
+                }
+                ActiveInput::Capture => {
+                    render_base(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Capture to Inbox:");
+                }
+                ActiveInput::AddSearch | ActiveInput::EditSearch => {
+                    render_page_select(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Search, e.g. state:pending tag:work due<7d \"text\"");
+                }
+                ActiveInput::RenameSearch => {
+                    render_page_select(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Rename Search:");
+                }
+                ActiveInput::AutoArchiveDays => {
+                    render_archive(frame, app_state);
+                    render_popup_input_field(frame, app_state, "Auto-archive after how many days? (empty = never)");
+                }
+                _ => (),
+            }
+        }
+        TodoModes::AddSelect => {
+            render_page(frame, app_state);
+            render_add_select(frame, app_state);
+        }
+        TodoModes::Detail => render_item_detail(frame, app_state),
+        TodoModes::Edit => {
+            render_page(frame, app_state);
+            if let Some(form) = &app_state.edit_form {
+                render_edit_form(frame, form);
+            }
+        }
+        TodoModes::Trash => render_trash(frame, app_state),
+        TodoModes::Archive => render_archive(frame, app_state),
+        TodoModes::MoveTo => {
+            render_page(frame, app_state);
+            render_move_to(frame, app_state);
+        }
+        TodoModes::Filter => {
+            render_page(frame, app_state);
+            render_filter(frame, app_state);
+        }
+        TodoModes::BlockerPicker => {
+            render_page(frame, app_state);
+            render_blocker_picker(frame, app_state);
+        }
+        TodoModes::Finder => {
+            if app_state.finder_return == TodoModes::PageSelect {
+                render_page_select(frame, app_state);
+            } else {
+                render_page(frame, app_state);
+            }
+            render_finder(frame, app_state);
+        }
+        TodoModes::Popup => render_base(frame, app_state),
+        TodoModes::Confirm => {
+            render_base(frame, app_state);
+            render_confirm_box(frame, app_state.alert_string_buffer.as_str());
+        }
+    }
+
+    if app_state.has_popup {
+        render_alert_box(frame, &app_state.alert_mode, app_state.alert_string_buffer.as_str());
+    }
+}
+
+fn render_base(frame: &mut Frame, app_state: &mut ApplicationState) { // Screen behind a popup
+    match app_state.popup_return {
+        TodoModes::PageSelect => render_page_select(frame, app_state),
+        TodoModes::Trash => render_trash(frame, app_state),
+        TodoModes::Archive => render_archive(frame, app_state),
+        TodoModes::BlockerPicker => {
+            render_page(frame, app_state);
+            render_blocker_picker(frame, app_state);
+        }
+        _ if app_state.selected_page().is_some() || app_state.virtual_page.is_some() => render_page(frame, app_state),
+        _ => (),
+    }
+}
+
+fn render_page(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(5)])
+        .split(frame.area());
+
+    let header = chunks[0]; let body = chunks[1]; let footer = chunks[2];
+ 
+    let mut title = match &app_state.virtual_page {
+        Some(search) if search.title == search.query => format!("Search: {}", search.title),
+        Some(search) => format!("Search: {}  [{}]", search.title, search.query),
+        None => format!("{} {}", "Page:", app_state.data.page_list[app_state.selected_page.unwrap()].title),
+    };
+    if !app_state.filter.is_empty() {
+        title.push_str(&format!("  [filter: {}]", app_state.filter.labels().join(" ")));
+    }
+    if app_state.virtual_page.is_none() {
+        let view = &app_state.data.page_list[app_state.selected_page.unwrap()].view;
+        if view.sort != SortOrder::Manual {
+            title.push_str(&format!("  [sort: {}]", view.sort.label()));
+        }
+        if view.hide_finished {
+            title.push_str("  [done hidden]");
+        }
+    }
+
+    let header_text = if app_state.mode == TodoModes::Search {
+        format!("/{}_  ({} match(es), [Enter] keep  [Esc] cancel)", app_state.search, app_state.search_matches().len())
+    } else {
+        app_state.status_message.clone()
+    };
+    let header_block = Paragraph::new(header_text)
+        .block(Block::default().borders(Borders::ALL).title("Header"));
+    let main_block = Block::default().borders(Borders::ALL).title(title);
+    let footer_block = Block::default().borders(Borders::ALL).title("Controls:");
+
+    frame.render_widget(header_block, header);
+    frame.render_widget(main_block.clone(), body);
+    frame.render_widget(footer_block, footer);
+
+    let inner_area = main_block.inner(body);
+    let inner1;
+    let mut inner2 = None;
+    if app_state.display_infobar {
+        let main_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(60),
+                Constraint::Percentage(40),
+            ]).split(inner_area);
+
+        inner1 = main_chunks[0]; 
+        inner2 = Some(main_chunks[1]);
+    }
+    else {
+        inner1 = inner_area;
+    }
+
+    let mut list_state = app_state.item_list_state;
+    let content_block = block_content_list(app_state);
+
+    frame.render_stateful_widget(content_block, inner1, &mut list_state);
+
+    // After the list, so it shows the freshly resolved selection
+    if let Some(inner2) = inner2 {
+        let info = Paragraph::new(Text::from(detail_lines(app_state)))
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title("Details"));
+        frame.render_widget(info, inner2);
+    }
+}
+
+// Metadata rows for the selected todo, shared by the side panel and the detail view
+fn item_fields(app_state: &ApplicationState) -> Option<Vec<(&'static str, String)>> {
+    let item = app_state.selected_item()?;
+    let group = app_state.selected_group()?;
+    let (done, total) = group.progress(app_state.selected_todo?);
+
+    let mut fields = vec![
+        ("Status", format!("{} {}", item.state.marker(), item.state.label())),
+        ("Group", group.title.clone()),
+        ("Due", item.due.map_or("none".to_string(), |due| due.to_string())),
+        ("Priority", item.priority.map_or("none", |p| p.label()).to_string()),
+        ("Repeat", item.recurrence.as_ref().map_or("never".to_string(), |rule| rule.to_string())),
+        ("Tags", if item.tags.is_empty() { "none".to_string() } else { tag_text(&item.tags) }),
+        ("Id", format!("#{}", item.id)),
+    ];
+    if let Some(reason) = &item.failed_reason {
+        fields.insert(1, ("Reason", reason.clone()));
+    }
+    if total > 0 {
+        fields.push(("Subtasks", format!("{}/{} done", done, total)));
+    }
+    if item.auto_complete {
+        fields.push(("Auto-complete", "on".to_string()));
+    }
+    let stamps = [("Created", item.created), ("Started", item.started), ("Completed", item.completed)];
+    for (name, at) in stamps {
+        if let Some(at) = at {
+            fields.push((name, at.format("%Y-%m-%d %H:%M").to_string()));
+        }
+    }
+    Some(fields)
+}
+
+fn tag_text(tags: &BTreeSet<String>) -> String {
+    tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")
+}
+
+fn detail_lines(app_state: &ApplicationState) -> Vec<Line<'_>> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = Vec::new();
+
+    if let (Some(item), Some(fields)) = (app_state.selected_item(), item_fields(app_state)) {
+        lines.push(Line::from(Span::styled(item.title.as_str(), bold)));
+        lines.push(Line::from(""));
+        for (name, value) in fields {
+            lines.push(Line::from(vec![Span::styled(format!("{}: ", name), bold), Span::raw(value)]));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Notes:", bold)));
+        if item.description.is_empty() {
+            lines.push(Line::from("(none)"));
+        }
+        else {
+            lines.extend(item.description.lines().map(Line::from));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Activity:", bold)));
+        lines.extend(activity_lines(item));
+    }
+    else if let Some(group) = app_state.selected_group() {
+        let done = group.item_list.iter().filter(|t| t.state == TodoState::Done).count();
+
+        lines.push(Line::from(Span::styled(group.title.as_str(), bold)));
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![Span::styled("Id: ", bold), Span::raw(format!("#{}", group.id))]));
+        lines.push(Line::from(format!("{} item(s), {} done", group.item_list.len(), done)));
+    }
+    else {
+        lines.push(Line::from("Nothing selected"));
+    }
+
+    lines
+}
+
+// Newest first, since that's usually what you're looking for
+fn activity_lines(item: &TodoItem) -> Vec<Line<'_>> {
+    if item.activity.is_empty() {
+        return vec![Line::from("(none)")];
+    }
+    item.activity.iter().rev().map(|a| Line::from(a.to_string())).collect()
+}
+
+fn render_item_detail(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let Some(item) = app_state.selected_item() else {
+        render_page(frame, app_state);
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(3)])
+        .split(frame.area());
+
+    let header = Paragraph::new(Line::from(Span::styled(
+            item.title.as_str(), Style::default().add_modifier(Modifier::BOLD))))
+        .block(Block::default().borders(Borders::ALL)
+            .title(Line::from("[Esc] back").alignment(Alignment::Right)));
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut fields = Vec::new();
+    for (name, value) in item_fields(app_state).unwrap_or_default() {
+        fields.push(Span::styled(format!("{}: ", name), bold));
+        fields.push(Span::raw(format!("{}   ", value)));
+    }
+
+    let mut lines = vec![Line::from(fields), Line::from(Span::styled("Notes:", bold))];
+    lines.extend(item.description.lines().map(|l| Line::from(format!("- {}", l))));
+
+    let links = app_state.detail_links();
+    let blockers = links.iter().filter(|id| item.blocked_by.contains(id)).count();
+    for (i, id) in links.iter().enumerate() {
+        if i == 0 || i == blockers {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(if i < blockers { "Blocked by:" } else { "Blocking:" }, bold)));
+        }
+        let Some((p, g, t)) = app_state.data.locate(*id) else {
+            continue;
+        };
+        let linked = &app_state.data.page_list[p].group_list[g].item_list[t];
+        let text = format!("  {} {}", linked.state.marker(), app_state.data.todo_path(p, g, linked));
+        let style = if i == app_state.detail_link { Style::default().fg(Color::Black).bg(Color::Green) } else { Style::default() };
+        lines.push(Line::from(Span::styled(text, style)));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Activity:", bold)));
+    lines.extend(activity_lines(item));
+
+    let body = Paragraph::new(Text::from(lines))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL));
+    let controls = if links.is_empty() {
+        "[e] edit  [Space] toggle  [Esc] back"
+    } else {
+        "[e] edit  [Space] toggle  [j/k] pick link  [g] go to  [Esc] back"
+    };
+    let footer = Paragraph::new(controls)
+        .block(Block::default().borders(Borders::ALL));
+
+    frame.render_widget(header, chunks[0]);
+    frame.render_widget(body, chunks[1]);
+    frame.render_widget(footer, chunks[2]);
+}
+
+fn block_content_list(app_state: &mut ApplicationState) -> List<'_> {
+    if app_state.virtual_page.is_some() {
+        return block_search_list(app_state);
+    }
+
+    let block = Block::default().borders(Borders::ALL);
+    app_state.list_length = 0;
+    
+    let mut items:Vec <ListItem> = Vec::new();
+    let mapping = app_state.page_rows();
+    let matches = app_state.search_matches();
+    let match_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+
+    let page = &app_state.data.page_list[app_state.selected_page.unwrap()];
+    {
+        for (row, &(group_index, todo_index)) in mapping.iter().enumerate() {
+            let group = &page.group_list[group_index];
+            let matched = matches.contains(&row);
+            let Some(todo_index) = todo_index else {
+                let prefix_group = if group.show_items { "▼" } else { "▶" };
+
+                let group_string = format!("{} {}", prefix_group, group.title.clone());
+                let mut style = Style::default().add_modifier(Modifier::BOLD);
+                if matched {
+                    style = style.patch(match_style);
+                }
+                items.push(ListItem::new(Line::from(Span::styled(group_string, style))));
+                continue;
+            };
+
+            let todo = &group.item_list[todo_index];
+            let (_, total) = group.progress(todo_index);
+            let fold = match (total, todo.folded) {
+                (0, _) => " ",
+                (_, false) => "▼",
+                (_, true) => "▶",
+            };
+
+            // Todo string 
+            let indent = "  ".repeat(todo.depth);
+            let mut spans = vec![Span::raw(format!("  {}{} ", indent, fold))];
+            let (todo_spans, style) = todo_spans(&app_state.data, group, todo_index);
+            spans.extend(todo_spans);
+            let style = if matched { match_style } else { style };
+
+            let mut lines = vec![Line::from(spans).style(style)];
+            if page.view.inline_descriptions {
+                let indent = format!("      {}", indent);
+                lines.extend(todo.description.lines().map(|l| Line::from(Span::styled(
+                    format!("{}{}", indent, l), Style::default().add_modifier(Modifier::DIM)))));
+            }
+            items.push(ListItem::new(Text::from(lines)));
+        }
+    }
+
+
+    app_state.list_length = mapping.len();
+
+    if mapping.is_empty() { // None selected
+        app_state.item_list_state.select(None);
+        app_state.selected_group = None;
+        app_state.selected_todo = None;
+    } else {
+        let selected = app_state.item_list_state.selected().unwrap_or(0);
+        let selected = selected.min(mapping.len() - 1);
+        app_state.item_list_state.select(Some(selected));
+
+        if let Some((group_index, todo_index)) = mapping.get(selected) {
+            app_state.selected_group = Some(*group_index);
+            app_state.selected_todo = *todo_index;
+        }
+    }
+
+    List::new(items).block(block)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green)
+        )
+}
+
+// A todo's row after the indent: state, priority, title and badges, with the row style
+fn todo_spans<'a>(data: &'a TodoData, group: &'a TodoGroup, todo_index: usize) -> (Vec<Span<'a>>, Style) {
+    let todo = &group.item_list[todo_index];
+    let (done, total) = group.progress(todo_index);
+    let mut spans = vec![
+        Span::styled(todo.state.marker(), Style::default().fg(todo.state.color())),
+        Span::raw(" "),
+    ];
+    if let Some(priority) = todo.priority {
+        spans.push(Span::styled(format!("{} ", priority.label()), Style::default().fg(priority.color())));
+    }
+    spans.push(Span::raw(todo.title.as_str()));
+    if total > 0 {
+        spans.push(Span::styled(format!(" {}/{}", done, total), Style::default().add_modifier(Modifier::DIM)));
+    }
+    if todo.recurrence.is_some() {
+        spans.push(Span::raw(" ↻"));
+    }
+    if let Some(due) = todo.due {
+        spans.push(Span::styled(format!("  (due {})", due), Style::default().add_modifier(Modifier::DIM)));
+    }
+    for tag in &todo.tags {
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!("#{}", tag), Style::default().fg(Color::Cyan).bg(Color::DarkGray)));
+    }
+    let blocked = todo.state != TodoState::Done && !data.open_blockers(todo).is_empty();
+    if blocked {
+        spans.push(Span::styled("  [blocked]", Style::default().fg(Color::Magenta)));
+    }
+    let style = match todo.state {
+        TodoState::Late => Style::default().fg(Color::Red),
+        _ if blocked => Style::default().fg(Color::DarkGray),
+        _ => Style::default(),
+    };
+    (spans, style)
+}
+
+// Results of the open saved search, each with the page and group it lives in
+fn block_search_list(app_state: &mut ApplicationState) -> List<'_> {
+    let rows = app_state.virtual_rows();
+    app_state.list_length = rows.len();
+
+    if rows.is_empty() {
+        app_state.item_list_state.select(None);
+        app_state.selected_group = None;
+        app_state.selected_todo = None;
+    } else {
+        let selected = app_state.item_list_state.selected().unwrap_or(0).min(rows.len() - 1);
+        app_state.item_list_state.select(Some(selected));
+        let (page_index, group_index, todo_index) = rows[selected];
+        app_state.selected_page = Some(page_index);
+        app_state.selected_group = Some(group_index);
+        app_state.selected_todo = Some(todo_index);
+    }
+
+    let app_state: &ApplicationState = app_state;
+    let items: Vec<ListItem> = rows.iter().map(|&(page_index, group_index, todo_index)| {
+        let page = &app_state.data.page_list[page_index];
+        let group = &page.group_list[group_index];
+        let (todo_spans, style) = todo_spans(&app_state.data, group, todo_index);
+        let mut spans = vec![Span::raw("  ")];
+        spans.extend(todo_spans);
+        spans.push(Span::styled(format!("  {} › {}", page.title, group.title), Style::default().add_modifier(Modifier::DIM)));
+        ListItem::new(Line::from(spans).style(style))
+    }).collect();
+
+    List::new(items).block(Block::default().borders(Borders::ALL))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green)
+        )
+}
+
+fn render_add_select(frame: &mut Frame, _app_state: &mut ApplicationState) {
+    let rect = frame.area().centered(Constraint::Length(30), Constraint::Length(5));
+
+    let text = Paragraph::new(Text::from(vec![
+            Line::from("(i) Add Item"),
+            Line::from("(s) Add Subtask"),
+            Line::from("(g) Add Group"),
+    ])).block(Block::default().borders(Borders::ALL).title("Select"));
+
+    frame.render_widget(text, rect);
+}
+
+fn render_page_select(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let area = frame.area().centered(
+        Constraint::Length(50),
+        Constraint::Length(25)
+    );
+
+    let menu = Block::default()
+        .borders(Borders::ALL)
+        .title(app_state.title.clone())
+        .title_alignment(Alignment::Center)
+        .title_bottom(app_state.status_message.as_str());
+
+    let entries = app_state.page_entries();
+    let inner = menu.inner(area);
+    frame.render_widget(menu, area);
+
+    let counts = SmartList::ALL.map(|list| app_state.smart_count(list));
+    let list = List::new(entries.iter().map(|entry| {
+        let line = match *entry {
+            PageEntry::Smart(list) => Line::from(Span::styled(format!("{} ({})", list.title(), counts[list as usize]),
+                Style::default().fg(Color::Yellow))),
+            PageEntry::Page(p) => Line::from(app_state.data.page_list[p].title.as_str()),
+            PageEntry::Search(s) => Line::from(Span::styled(format!("⌕ {}", app_state.data.searches[s].title),
+                Style::default().fg(Color::Cyan))),
+        };
+        ListItem::new(line.alignment(Alignment::Center))
+    }))
+    .highlight_symbol(">>")
+    .highlight_style(
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::Green),
+    );
+
+    if entries.len() > SmartList::ALL.len() {
+        frame.render_stateful_widget(list, inner, &mut app_state.page_list_state);
+        return;
+    }
+
+    // Only the built-in lists so far
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(entries.len() as u16 + 1), Constraint::Min(0)])
+        .split(inner);
+    frame.render_stateful_widget(list, chunks[0], &mut app_state.page_list_state);
+
+    let text = Paragraph::new(Text::from(vec![
+            Line::from("No pages found..."),
+            Line::from("Press \'a\' to create one."),
+            Line::from("Press \'t\' to open the trash."),
+            Line::from("Press \'s\' to save a search."),
+            Line::from("Press \'c\' to capture to the Inbox."),
+    ])).alignment(Alignment::Center);
+    frame.render_widget(text, chunks[1]);
+}
+
+fn render_trash(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let area = frame.area().centered(
+        Constraint::Length(60),
+        Constraint::Length(25)
+    );
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Trash")
+        .title(Line::from("[r] restore  [x] purge  [X] empty  [Esc] back").alignment(Alignment::Right))
+        .title_bottom(app_state.status_message.as_str());
+
+    if app_state.data.trash.is_empty() {
+        frame.render_widget(Paragraph::new("Trash is empty.").block(block), area);
+        return;
+    }
+
+    // Most recently deleted first
+    let list = List::new(app_state.data.trash
+        .iter().rev().map(|entry| ListItem::new(Line::from(entry.label()))))
+        .block(block)
+        .highlight_symbol(">>")
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green),
+        );
+
+    frame.render_stateful_widget(list, area, &mut app_state.trash_list_state);
+}
+
+fn render_archive(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let area = frame.area().centered(
+        Constraint::Length(70),
+        Constraint::Length(25)
+    );
+
+    let title = match app_state.archive_page.and_then(|p| app_state.data.page_list.get(p)) {
+        Some(page) => format!("Archive: {}", page.title),
+        None => "Archive: all pages".to_string(),
+    };
+    let policy = match app_state.data.auto_archive_days {
+        _ if !app_state.status_message.is_empty() => app_state.status_message.clone(),
+        Some(days) => format!("Auto-archive after {} day(s)", days),
+        None => "Auto-archive off".to_string(),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title(Line::from("[r] restore  [a] auto-archive  [Esc] back").alignment(Alignment::Right))
+        .title_bottom(policy);
+
+    let entries = app_state.archive_entries();
+    if entries.is_empty() {
+        frame.render_widget(Paragraph::new("Nothing archived.").block(block), area);
+        return;
+    }
+
+    let all_pages = app_state.archive_page.is_none();
+    let list = List::new(entries.iter().map(|&(p, e)| {
+            let page = &app_state.data.page_list[p];
+            let label = page.archive[e].label();
+            ListItem::new(Line::from(if all_pages { format!("{} › {}", page.title, label) } else { label }))
+        }))
+        .block(block)
+        .highlight_symbol(">>")
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green),
+        );
+
+    frame.render_stateful_widget(list, area, &mut app_state.archive_list_state);
+}
+
+fn render_move_to(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let area = frame.area().centered(
+        Constraint::Length(50),
+        Constraint::Length(15)
+    );
+
+    let destinations = app_state.move_destinations();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Move to…");
+
+    frame.render_widget(Clear, area);
+    if destinations.is_empty() {
+        frame.render_widget(Paragraph::new("No other groups to move to.").block(block), area);
+        return;
+    }
+
+    let list = List::new(destinations
+        .into_iter().map(|(_, _, label)| ListItem::new(Line::from(label))))
+        .block(block)
+        .highlight_symbol(">>")
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green),
+        );
+
+    frame.render_stateful_widget(list, area, &mut app_state.move_list_state);
+}
+
+fn render_popup_input_field(frame: &mut Frame, app_state: &mut ApplicationState, title: &str) {
+    let rect = frame.area().centered(Constraint::Length(50), Constraint::Length(3));
+    let textbox = Block::default().borders(Borders::ALL).title(title);
+    let buffer = Paragraph::new(app_state.buffer_string.clone()).block(textbox);
+    
+    frame.render_widget(buffer, rect); 
+}
+
+fn render_tag_suggestions(frame: &mut Frame, app_state: &ApplicationState) {
+    let suggestions = app_state.tag_suggestions();
+    if suggestions.is_empty() {
+        return;
+    }
+
+    // Just below the input field
+    let input = frame.area().centered(Constraint::Length(50), Constraint::Length(3));
+    let height = (suggestions.len() as u16).min(5) + 2;
+    let rect = Rect { y: input.bottom(), height, ..input }.intersection(frame.area());
+
+    let lines: Vec<Line> = suggestions.iter().take(5).map(|t| Line::from(format!("#{}", t))).collect();
+    frame.render_widget(Clear, rect);
+    frame.render_widget(Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title("Suggestions")), rect);
+}
+
+fn render_filter(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let area = frame.area().centered(
+        Constraint::Length(40),
+        Constraint::Length(22)
+    );
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Filters")
+        .title_bottom("[Space] toggle  [c] clear  [Esc] done");
+
+    frame.render_widget(Clear, area);
+    let list = List::new(app_state.filter_criteria().iter().map(|criterion| {
+            let mark = if app_state.filter.is_checked(criterion) { "[x]" } else { "[ ]" };
+            ListItem::new(Line::from(format!("{} {}", mark, criterion.label())))
+        }))
+        .block(block)
+        .highlight_symbol(">>")
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green),
+        );
+
+    frame.render_stateful_widget(list, area, &mut app_state.filter_list_state);
+}
+
+fn render_blocker_picker(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let area = frame.area().centered(
+        Constraint::Length(70),
+        Constraint::Length(20)
+    );
+
+    let candidates = app_state.blocker_candidates();
+    let blocked_by = app_state.selected_item().map(|t| t.blocked_by.clone()).unwrap_or_default();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Blocked by…")
+        .title_bottom("[Space] toggle  [Esc] done");
+
+    frame.render_widget(Clear, area);
+    if candidates.is_empty() {
+        frame.render_widget(Paragraph::new("No other todos to wait on.").block(block), area);
+        return;
+    }
+
+    let list = List::new(candidates.into_iter().map(|(id, label)| {
+            let mark = if blocked_by.contains(&id) { "[x]" } else { "[ ]" };
+            ListItem::new(Line::from(format!("{} {}", mark, label)))
+        }))
+        .block(block)
+        .highlight_symbol(">>")
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green),
+        );
+
+    frame.render_stateful_widget(list, area, &mut app_state.blocker_list_state);
+}
+
+fn render_finder(frame: &mut Frame, app_state: &mut ApplicationState) {
+    let area = frame.area().centered(
+        Constraint::Length(70),
+        Constraint::Length(20)
+    );
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Find")
+        .title_bottom("[Enter] go  [↑/↓] pick  [Esc] close");
+    let inner = block.inner(area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    frame.render_widget(Paragraph::new(format!("> {}_", app_state.finder)), chunks[0]);
+
+    let results = app_state.finder_results();
+    if results.is_empty() {
+        frame.render_widget(Paragraph::new("No matches."), chunks[1]);
+        return;
+    }
+
+    let pages = &app_state.data.page_list;
+    let list = List::new(results.into_iter().map(|(target, path)| {
+            let kind = match (target.group, target.todo) {
+                (Some(g), Some(t)) => pages[target.page].group_list[g].item_list[t].state.marker(),
+                (Some(_), None) => "grp",
+                _ => "pg",
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:4}", kind), Style::default().add_modifier(Modifier::DIM)),
+                Span::raw(path),
+            ]))
+        }))
+        .highlight_symbol(">>")
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Green),
+        );
+
+    frame.render_stateful_widget(list, chunks[1], &mut app_state.finder_list_state);
+}
+
+fn render_alert_box(frame: &mut Frame, alert_mode: &AlertMode, message: &str) {
+    let rect = frame.area().centered(Constraint::Length(50), Constraint::Length(7));
+
+    let _title = match alert_mode {
+        AlertMode::Message => "Message...",
+        AlertMode::Warning => "Warning!",
+        AlertMode::Error => "Error!",
+        _ => "None",
+    };
+    let block = Block::default().borders(Borders::ALL).title(_title);
+    let paragraph = Paragraph::new(message).block(block);
+    frame.render_widget(paragraph, rect);
+}
+
+fn render_edit_form(frame: &mut Frame, form: &EditForm) {
+    let rect = frame.area().centered(Constraint::Length(80), Constraint::Length(12));
+
+    let focused = Style::default().fg(Color::Black).bg(Color::Green);
+    let label = |text: &'static str, field: EditField| {
+        if form.focus == field { Span::styled(text, focused) } else { Span::raw(text) }
+    };
+    let cursor = |field: EditField| if form.focus == field { "_" } else { "" };
+
+    let mut status = vec![label("Status:  ", EditField::Status)];
+    for state in TodoState::ALL {
+        let mark = if state == form.item.state { "(•)" } else { "( )" };
+        status.push(Span::raw(format!(" {} {} ", mark, state.label())));
+    }
+
+    let mut priority = vec![label("Priority:", EditField::Priority)];
+    for option in EditForm::PRIORITIES {
+        let mark = if option == form.item.priority { "(•)" } else { "( )" };
+        priority.push(Span::raw(format!(" {} {} ", mark, option.map_or("None", |p| p.label()))));
+    }
+
+    let mut lines = vec![
+        Line::from(vec![
+            label("Title:   ", EditField::Title),
+            Span::raw(format!(" [ {}{} ]", form.item.title, cursor(EditField::Title))),
+        ]),
+        Line::from(status),
+        Line::from(vec![
+            label("Due:     ", EditField::Due),
+            Span::raw(format!(" [ {}{} ]", form.due_text, cursor(EditField::Due))),
+            Span::styled("  YYYY-MM-DD [HH:MM], today, +3d", Style::default().add_modifier(Modifier::DIM)),
+        ]),
+        Line::from(vec![
+            label("Repeat:  ", EditField::Repeat),
+            Span::raw(format!(" [ {}{} ]", form.repeat_text, cursor(EditField::Repeat))),
+            Span::styled("  daily, weekly [mon,thu], monthly, every 3d, after 3d", Style::default().add_modifier(Modifier::DIM)),
+        ]),
+        Line::from(priority),
+        Line::from(vec![
+            label("Notes:   ", EditField::Description),
+            Span::raw(format!(" [ {}{} ]", form.item.description, cursor(EditField::Description))),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            label("[Save]", EditField::Save),
+            Span::raw("  "),
+            label("[Discard]", EditField::Discard),
+        ]),
+    ];
+    if let Some(error) = &form.error {
+        lines.push(Line::from(Span::styled(error.as_str(), Style::default().fg(Color::Red))));
+    }
+
+    let title = if form.is_new() { "NEW TASK" } else { "EDIT TASK" };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title(Line::from("[Tab] next  [Esc] cancel").alignment(Alignment::Right));
+    frame.render_widget(Paragraph::new(Text::from(lines)).block(block), rect);
+}
+
+fn render_confirm_box(frame: &mut Frame, message: &str) {
+    let rect = frame.area().centered(Constraint::Length(50), Constraint::Length(7));
+
+    let mut lines: Vec<Line> = message.lines().map(Line::from).collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from("(y) Yes  (n) No").alignment(Alignment::Center));
+
+    let block = Block::default().borders(Borders::ALL).title("Confirm");
+    frame.render_widget(Paragraph::new(Text::from(lines)).block(block), rect);
+}
+
+// ----------------------------- END OF RENDER -----------------------------
+
+// --------------------------------- INPUT ---------------------------------
+
+fn handle_input(key: KeyEvent, app_state: & mut ApplicationState) { // Routes input 
+    app_state.status_message.clear();
+
+    // Undo/redo work from any screen that isn't taking text or a choice
+    if matches!(app_state.mode, TodoModes::Normal | TodoModes::PageSelect | TodoModes::Detail | TodoModes::Trash
+        | TodoModes::Archive) {
+        match key.code {
+            KeyCode::Char('u') => {
+                app_state.undo();
+                return;
+            }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app_state.redo();
+                return;
+            }
+            _ => (),
+        }
+    }
+
+    match app_state.mode {
+        TodoModes::Normal => handle_normal_input(key, app_state),
+        TodoModes::PageSelect => handle_page_select_input(key, app_state),
+        TodoModes::Insert => handle_insert(key, app_state),
+        TodoModes::AddSelect => handle_add_select_input(key, app_state),
+        TodoModes::Popup => handle_alert_box(key, app_state),
+        TodoModes::Confirm => handle_confirm_box(key, app_state),
+        TodoModes::Edit => handle_edit_form(key, app_state),
+        TodoModes::Detail => handle_detail_input(key, app_state),
+        TodoModes::Trash => handle_trash_input(key, app_state),
+        TodoModes::Archive => handle_archive_input(key, app_state),
+        TodoModes::MoveTo => handle_move_to_input(key, app_state),
+        TodoModes::Filter => handle_filter_input(key, app_state),
+        TodoModes::BlockerPicker => handle_blocker_picker_input(key, app_state),
+        TodoModes::Search => handle_search_input(key, app_state),
+        TodoModes::Finder => handle_finder_input(key, app_state),
+    }
+}
+
+fn handle_page_select_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    let len = app_state.page_entries().len();
+
+    match key.code {
+        KeyCode::Esc => app_state.should_quit = true,
+        KeyCode::Char('k') | KeyCode::Up if len > 0 => { 
+            let up = match app_state.page_list_state.selected() {
+                Some(up) => {
+                    if up == 0 {len - 1} else {up - 1}
+                },
+                None => 0,
+            };
+            app_state.page_list_state.select(Some(up));
+            app_state.clamp_page_selection();
+        }
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            let down = match app_state.page_list_state.selected() {
+                Some(down) => { 
+                    if down >= len - 1 {0} else {down + 1}
+                },
+                None => 0,
+            };
+            app_state.page_list_state.select(Some(down));
+            app_state.clamp_page_selection();
+        }
+        KeyCode::Enter => match app_state.selected_entry() {
+            Some(PageEntry::Smart(list)) => app_state.open_smart(list),
+            Some(PageEntry::Page(_)) => app_state.open_page(),
+            Some(PageEntry::Search(search)) => app_state.open_search(app_state.data.searches[search].clone()),
+            None => (),
+        },
+        KeyCode::Char('c') => app_state.start_capture(),
+        KeyCode::Char('s') => { // Save a search
+            app_state.mode = TodoModes::Insert;
+            app_state.input_mode = ActiveInput::AddSearch;
+        }
+        KeyCode::Char('e') => {
+            if let Some(PageEntry::Search(search)) = app_state.selected_entry() {
+                app_state.buffer_string = app_state.data.searches[search].query.clone();
+                app_state.mode = TodoModes::Insert;
+                app_state.input_mode = ActiveInput::EditSearch;
+            }
+        }
+
+        KeyCode::Char('a') => { // Add page
+            app_state.mode = TodoModes::Insert;
+            app_state.input_mode = ActiveInput::AddPage;
+        }
+        KeyCode::Char('r') => match app_state.selected_entry() {
+            Some(PageEntry::Page(page)) => {
+                app_state.buffer_string = app_state.data.page_list[page].title.clone();
+                app_state.selected_page = Some(page);
+                app_state.mode = TodoModes::Insert;
+                app_state.input_mode = ActiveInput::RenamePage;
+            }
+            Some(PageEntry::Search(search)) => {
+                app_state.buffer_string = app_state.data.searches[search].title.clone();
+                app_state.mode = TodoModes::Insert;
+                app_state.input_mode = ActiveInput::RenameSearch;
+            }
+            _ => (),
+        },
+        KeyCode::Char('x') => match app_state.selected_entry() {
+            Some(PageEntry::Page(page)) => {
+                let message = format!("Delete page \"{}\"?\nIt can be restored from the trash.", app_state.data.page_list[page].title);
+                app_state.confirm_box(ConfirmAction::DeletePage(page), message);
+            }
+            Some(PageEntry::Search(search)) => { // Cheap to make again, and undo brings it back anyway
+                app_state.commit(Mutation::DeleteSearch { search });
+                app_state.clamp_page_selection();
+                app_state.status_message = "Deleted the search, [u] to undo".to_string();
+            }
+            _ => (),
+        },
+        KeyCode::Char('K') | KeyCode::Char('J') => {
+            if let Some(PageEntry::Page(page)) = app_state.selected_entry() {
+                let up = key.code == KeyCode::Char('K');
+                let id = app_state.data.page_list[page].id;
+                app_state.commit(Mutation::MovePage { page, up });
+                if let Some(moved) = app_state.data.page_list.iter().position(|p| p.id == id) {
+                    app_state.select_page(moved);
+                }
+            }
+        }
+        KeyCode::Char('t') => {
+            let selected = if app_state.data.trash.is_empty() { None } else { Some(0) };
+            app_state.trash_list_state.select(selected);
+            app_state.mode = TodoModes::Trash;
+        }
+        KeyCode::Char('v') => app_state.open_archive(None),
+        KeyCode::Char('f') => app_state.open_finder(),
+        _ => (),
+    }
+}
+
+fn handle_normal_input(key: KeyEvent, app_state: &mut ApplicationState) { // MAIN SHIT
+    // A saved search lists todos from all over, so only keys acting on the highlighted todo apply
+    if app_state.virtual_page.is_some() && !matches!(key.code, KeyCode::Esc | KeyCode::Up | KeyCode::Down | KeyCode::Enter
+        | KeyCode::Char('j' | 'k' | ' ' | '1'..='4' | 'e' | 'p' | '+' | '=' | '-' | 'r' | '#' | 'x' | 'b' | 'f' | 'c')) {
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc => app_state.mode = TodoModes::PageSelect,
+        KeyCode::Char('a') => app_state.mode = TodoModes::AddSelect,
+        KeyCode::Char('f') => app_state.open_finder(),
+        KeyCode::Char('c') => app_state.start_capture(),
+        KeyCode::Char('/') => {
+            app_state.search.clear();
+            app_state.search_origin = app_state.item_list_state.selected();
+            app_state.mode = TodoModes::Search;
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') if !app_state.search.is_empty() => {
+            let from = app_state.item_list_state.selected().unwrap_or(0);
+            if !app_state.jump_to_match(from, key.code == KeyCode::Char('n'), false) {
+                app_state.status_message = format!("No match for \"{}\"", app_state.search);
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app_state.selected_item_up();
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            app_state.selected_item_down();
+        }
+        KeyCode::Char(' ') => {
+            let (Some(page), Some(group)) = (app_state.selected_page, app_state.selected_group) else {
+                return;
+            };
+
+            match app_state.selected_todo {
+                Some(_) => app_state.change_state(None, None),
+                None => app_state.commit(Mutation::ToggleShowItems { page, group }),
+            }
+        }
+        KeyCode::Char('1') => app_state.change_state(Some(TodoState::Pending), None),
+        KeyCode::Char('2') => app_state.change_state(Some(TodoState::Doing), None),
+        KeyCode::Char('3') => app_state.change_state(Some(TodoState::Done), None),
+        KeyCode::Char('4') if app_state.selected_item().is_some() => { // Asks for a reason first
+            app_state.buffer_string.clear();
+            app_state.input_mode = ActiveInput::FailReason;
+            app_state.mode = TodoModes::Insert;
+        }
+        KeyCode::Char('e') => {
+            if let (Some(page), Some(group), Some(todo)) =
+                (app_state.selected_page, app_state.selected_group, app_state.selected_todo) {
+                let form = EditForm::edit(page, group, todo, app_state.selected_item().unwrap());
+                app_state.edit_form = Some(form);
+                app_state.mode = TodoModes::Edit;
+            }
+        }
+        KeyCode::Char('p') => app_state.display_infobar = !app_state.display_infobar,
+        KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char('-') => {
+            let (Some(page), Some(group), Some(todo), Some(item)) = (app_state.selected_page,
+                app_state.selected_group, app_state.selected_todo, app_state.selected_item()) else {
+                return;
+            };
+
+            let priority = if key.code == KeyCode::Char('-') {
+                Priority::lower(item.priority)
+            } else {
+                Priority::raise(item.priority)
+            };
+            if priority != item.priority {
+                app_state.commit(Mutation::SetPriority { page, group, todo, priority });
+            }
+        }
+        KeyCode::Char('s') => { // Sort the highlighted group, keeping the highlight on the same todo
+            let (Some(page), Some(group)) = (app_state.selected_page, app_state.selected_group) else {
+                return;
+            };
+            let selected_id = app_state.selected_item().map(|item| item.id);
+
+            app_state.commit(Mutation::SortGroup { page, group });
+            app_state.select_todo_by_id(group, selected_id);
+        }
+        KeyCode::Char('r') => {
+            if let Some(item) = app_state.selected_item() {
+                app_state.buffer_string = item.title.clone();
+                app_state.input_mode = ActiveInput::RenameTodo;
+            }
+            else if let Some(group) = app_state.selected_group() {
+                app_state.buffer_string = group.title.clone();
+                app_state.input_mode = ActiveInput::RenameGroup;
+            }
+            else {
+                return;
+            }
+            app_state.mode = TodoModes::Insert;
+        }
+        KeyCode::Char('K') | KeyCode::Char('J') => {
+            let (Some(page), Some(group)) = (app_state.selected_page, app_state.selected_group) else {
+                return;
+            };
+            let up = key.code == KeyCode::Char('K');
+
+            match app_state.selected_todo {
+                Some(_) if app_state.selected_page().is_some_and(|p| p.view.sort != SortOrder::Manual) => {
+                    app_state.status_message = "Switch to manual order ([o]) to move todos".to_string();
+                }
+                Some(todo) => {
+                    let id = app_state.selected_item().map(|t| t.id);
+                    app_state.commit(Mutation::MoveTodo { page, group, todo, up });
+                    app_state.select_todo_by_id(group, id);
+                }
+                None => {
+                    let len = app_state.selected_page().map_or(0, |p| p.group_list.len());
+                    if (up && group == 0) || (!up && group + 1 >= len) {
+                        return;
+                    }
+                    app_state.commit(Mutation::MoveGroup { page, group, up });
+                    app_state.select_row(if up { group - 1 } else { group + 1 }, None);
+                }
+            }
+        }
+        KeyCode::Char('>') | KeyCode::Char('<') => {
+            let (Some(page), Some(group), Some(todo)) =
+                (app_state.selected_page, app_state.selected_group, app_state.selected_todo) else {
+                return;
+            };
+            let id = app_state.selected_item().map(|t| t.id);
+            app_state.commit(Mutation::IndentTodo { page, group, todo, outdent: key.code == KeyCode::Char('<') });
+            app_state.select_todo_by_id(group, id);
+        }
+        KeyCode::Char('z') => {
+            if let (Some(page), Some(group), Some(todo), Some(g)) = (app_state.selected_page,
+                app_state.selected_group, app_state.selected_todo, app_state.selected_group())
+                && g.progress(todo).1 > 0 {
+                app_state.commit(Mutation::FoldTodo { page, group, todo });
+            }
+        }
+        KeyCode::Char('C') => {
+            if let (Some(page), Some(group), Some(todo)) =
+                (app_state.selected_page, app_state.selected_group, app_state.selected_todo) {
+                app_state.commit(Mutation::ToggleAutoComplete { page, group, todo });
+            }
+        }
+        KeyCode::Char('b') if app_state.selected_item().is_some() => {
+            let candidates = app_state.blocker_candidates().len();
+            app_state.blocker_list_state.select(if candidates == 0 { None } else { Some(0) });
+            app_state.mode = TodoModes::BlockerPicker;
+        }
+        KeyCode::Char('#') => {
+            if let Some(item) = app_state.selected_item() {
+                app_state.buffer_string = tag_text(&item.tags);
+                if !app_state.buffer_string.is_empty() {
+                    app_state.buffer_string.push(' ');
+                }
+                app_state.input_mode = ActiveInput::EditTags;
+                app_state.mode = TodoModes::Insert;
+            }
+        }
+        KeyCode::Char('t') | KeyCode::Char('F') => {
+            app_state.filter_list_state.select(Some(0));
+            app_state.mode = TodoModes::Filter;
+        }
+        KeyCode::Char('m') if app_state.selected_item().is_some() => {
+            let destinations = app_state.move_destinations().len();
+            app_state.move_list_state.select(if destinations == 0 { None } else { Some(0) });
+            app_state.mode = TodoModes::MoveTo;
+        }
+        KeyCode::Char('x') => {
+            let (Some(page), Some(group)) = (app_state.selected_page, app_state.selected_group) else {
+                return;
+            };
+
+            let (action, message) = match (app_state.selected_todo, app_state.selected_item(), app_state.selected_group()) {
+                (Some(todo), Some(item), Some(g)) => {
+                    let subtasks = g.subtree_end(todo) - todo - 1;
+                    let message = if subtasks == 0 {
+                        format!("Delete todo \"{}\"?", item.title)
+                    } else {
+                        format!("Delete todo \"{}\" and its {} subtask(s)?", item.title, subtasks)
+                    };
+                    (ConfirmAction::DeleteTodo(page, group, todo), message)
+                }
+                (_, _, Some(g)) => (ConfirmAction::DeleteGroup(page, group),
+                    format!("Delete group \"{}\" and its {} todo(s)?", g.title, g.item_list.len())),
+                _ => return,
+            };
+            app_state.confirm_box(action, format!("{}\nIt can be restored from the trash.", message));
+        }
+        KeyCode::Char('X') => {
+            if let (Some(page), Some(group), Some(g)) = (app_state.selected_page, app_state.selected_group, app_state.selected_group())
+                && !g.item_list.is_empty() {
+                let message = format!("Move all {} todo(s) in \"{}\" to the trash?", g.item_list.len(), g.title);
+                app_state.confirm_box(ConfirmAction::ClearGroup(page, group), message);
+            }
+        }
+        KeyCode::Char('A') => { // Archive the group's done todos
+            let (Some(page), Some(group), Some(g)) = (app_state.selected_page, app_state.selected_group, app_state.selected_group()) else {
+                return;
+            };
+            let done = g.item_list.iter().filter(|t| t.depth == 0 && t.state == TodoState::Done).count();
+            if done == 0 {
+                app_state.status_message = format!("Nothing done to archive in \"{}\"", g.title);
+                return;
+            }
+
+            let selected = app_state.selected_item().map(|t| t.id);
+            app_state.commit(Mutation::ArchiveDone { page, group });
+            app_state.select_row(group, None);
+            app_state.select_todo_by_id(group, selected);
+            app_state.status_message = format!("Archived {} todo(s), [v] to view", done);
+        }
+        KeyCode::Char('v') => app_state.open_archive(app_state.selected_page),
+        KeyCode::Char('H') => app_state.change_view(|view| {
+            view.hide_finished = !view.hide_finished;
+            if view.hide_finished { "Hiding done and failed todos" } else { "Showing done and failed todos" }.to_string()
+        }),
+        KeyCode::Char('o') => app_state.change_view(|view| {
+            view.sort = view.sort.next();
+            format!("Sorted by {}", view.sort.label())
+        }),
+        KeyCode::Char('Z') => app_state.change_view(|view| {
+            view.start_collapsed = !view.start_collapsed;
+            if view.start_collapsed { "Groups start collapsed" } else { "Groups start expanded" }.to_string()
+        }),
+        KeyCode::Char('i') => app_state.change_view(|view| {
+            view.inline_descriptions = !view.inline_descriptions;
+            if view.inline_descriptions { "Showing descriptions inline" } else { "Hiding inline descriptions" }.to_string()
+        }),
+        KeyCode::Enter if app_state.selected_item().is_some() => {
+            app_state.detail_link = 0;
+            app_state.mode = TodoModes::Detail;
+        }
+        _ => (),
+    }
+}
+
+fn handle_detail_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter => app_state.mode = TodoModes::Normal,
+        KeyCode::Char('e') | KeyCode::Char(' ') | KeyCode::Char('x') | KeyCode::Char('r') => handle_normal_input(key, app_state),
+        KeyCode::Char('1'..='4') => handle_normal_input(key, app_state),
+        KeyCode::Char('j') | KeyCode::Down => {
+            let len = app_state.detail_links().len();
+            if len > 0 {
+                app_state.detail_link = (app_state.detail_link + 1) % len;
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            let len = app_state.detail_links().len();
+            if len > 0 {
+                app_state.detail_link = (app_state.detail_link + len - 1) % len;
+            }
+        }
+        KeyCode::Char('g') => {
+            let links = app_state.detail_links();
+            if let Some(id) = links.get(app_state.detail_link)
+                && let Some((page, group, todo)) = app_state.data.locate(*id) {
+                app_state.reveal(page, group, todo);
+                app_state.detail_link = 0;
+            }
+        }
+        _ => (),
+    }
+}
+
+fn handle_add_select_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    match key.code {
+        KeyCode::Char('i') => {
+            if let (Some(page), Some(group)) = (app_state.selected_page, app_state.selected_group) {
+                app_state.edit_form = Some(EditForm::create(page, group));
+                app_state.mode = TodoModes::Edit;
+            }
+            else {
+                // TODO: have it create a new group "Untitled" and add todo on it. 
+                app_state.mode = TodoModes::Normal;
+                app_state.alert_box(AlertMode::Error,
+                "Please have a group selected/highlighted \n 
+                to create a todo item".to_string());
+            }
+        }
+        KeyCode::Char('s') => {
+            if let (Some(page), Some(group), Some(parent)) =
+                (app_state.selected_page, app_state.selected_group, app_state.selected_todo) {
+                app_state.edit_form = Some(EditForm::create_subtask(page, group, parent));
+                app_state.mode = TodoModes::Edit;
+            }
+            else {
+                app_state.mode = TodoModes::Normal;
+                app_state.alert_box(AlertMode::Error, "Highlight a todo to add a subtask to it".to_string());
+            }
+        }
+        KeyCode::Char('g') => { 
+            app_state.mode = TodoModes::Insert; 
+            app_state.input_mode = ActiveInput::AddGroup;
+        }
+        KeyCode::Esc => {
+            app_state.mode = TodoModes::Normal;
+            app_state.input_mode = ActiveInput::None;
+        }
+        _ => (),
+    }
+}
+
+fn handle_insert(key: KeyEvent, app_state: &mut ApplicationState) {
+    match key.code {
+        KeyCode::Char(c) => {
+            app_state.buffer_string.push(c);
+        }
+        KeyCode::Backspace => {
+            app_state.buffer_string.pop();
+        }
+        KeyCode::Esc => { // Cancel
+            app_state.buffer_string.clear();
+            match app_state.input_mode {
+                ActiveInput::AddPage | ActiveInput::RenamePage | ActiveInput::AddSearch | ActiveInput::EditSearch
+                    | ActiveInput::RenameSearch => {
+                    app_state.mode = TodoModes::PageSelect;
+                    app_state.input_mode = ActiveInput::None;
+                }
+                ActiveInput::AddGroup | ActiveInput::RenameGroup | ActiveInput::RenameTodo | ActiveInput::EditTags
+                    | ActiveInput::FailReason => {
+                    app_state.mode = TodoModes::Normal;
+                    app_state.input_mode = ActiveInput::None;
+                }
+                ActiveInput::AutoArchiveDays => {
+                    app_state.mode = TodoModes::Archive;
+                    app_state.input_mode = ActiveInput::None;
+                }
+                ActiveInput::Capture => {
+                    app_state.mode = app_state.popup_return;
+                    app_state.input_mode = ActiveInput::None;
+                }
+                _ => (),
+            }
+        }
+        KeyCode::Tab if matches!(app_state.input_mode, ActiveInput::EditTags) => {
+            if let Some(tag) = app_state.tag_suggestions().into_iter().next() {
+                let keep = app_state.buffer_string.rfind(' ').map_or(0, |i| i + 1);
+                app_state.buffer_string.truncate(keep);
+                app_state.buffer_string.push_str(&format!("#{} ", tag));
+            }
+        }
+        KeyCode::Enter if matches!(app_state.input_mode, ActiveInput::FailReason) => { // Empty means no reason
+            let reason = app_state.buffer_string.trim().to_string();
+            app_state.buffer_string.clear();
+            app_state.mode = TodoModes::Normal;
+            app_state.input_mode = ActiveInput::None;
+            app_state.change_state(Some(TodoState::Failed), (!reason.is_empty()).then_some(reason));
+        }
+        KeyCode::Enter if matches!(app_state.input_mode, ActiveInput::AddSearch | ActiveInput::EditSearch) => {
+            let query = app_state.buffer_string.trim().to_string();
+            if query.is_empty() {
+                return;
+            }
+            if let Err(e) = Query::parse(&query) {
+                app_state.status_message = e; // Keeps the prompt open to fix it
+                return;
+            }
+
+            match (&app_state.input_mode, app_state.selected_entry()) {
+                (ActiveInput::EditSearch, Some(PageEntry::Search(search))) => {
+                    app_state.commit(Mutation::EditSearch { search, query });
+                }
+                _ => {
+                    app_state.commit(Mutation::AddSearch { query });
+                    app_state.page_list_state.select(Some(app_state.page_entries().len() - 1));
+                    app_state.clamp_page_selection();
+                }
+            }
+            app_state.buffer_string.clear();
+            app_state.mode = TodoModes::PageSelect;
+            app_state.input_mode = ActiveInput::None;
+        }
+        KeyCode::Enter if matches!(app_state.input_mode, ActiveInput::AutoArchiveDays) => { // Empty turns it off
+            let text = app_state.buffer_string.trim().trim_end_matches('d').to_string();
+            let days = match text.parse::<u32>() {
+                _ if text.is_empty() => None,
+                Ok(days) if days > 0 && days <= archive::MAX_AUTO_ARCHIVE_DAYS => Some(days),
+                _ => {
+                    app_state.status_message = format!("Can't read \"{}\", use 1 to {} days", text, archive::MAX_AUTO_ARCHIVE_DAYS);
+                    return;
+                }
+            };
+
+            app_state.buffer_string.clear();
+            app_state.mode = TodoModes::Archive;
+            app_state.input_mode = ActiveInput::None;
+            app_state.commit(Mutation::SetAutoArchive { days });
+        }
+        KeyCode::Enter if matches!(app_state.input_mode, ActiveInput::EditTags) => { // Empty clears the tags
+            if let (Some(page), Some(group), Some(todo)) =
+                (app_state.selected_page, app_state.selected_group, app_state.selected_todo) {
+                let tags = app_state.buffer_string.split_whitespace()
+                    .map(normalize_tag)
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                app_state.commit(Mutation::SetTags { page, group, todo, tags });
+            }
+
+            app_state.buffer_string.clear();
+            app_state.mode = TodoModes::Normal;
+            app_state.input_mode = ActiveInput::None;
+        }
+        KeyCode::Enter => {
+            app_state.buffer_string = app_state.buffer_string.trim().to_string();
+            if !app_state.buffer_string.is_empty() {
+                match app_state.input_mode {
+                    ActiveInput::AddPage => {
+                        app_state.commit(Mutation::AddPage { title: app_state.buffer_string.clone() });
+                        app_state.clamp_page_selection(); // The new page lands before the saved searches
+
+                        app_state.mode = TodoModes::PageSelect;
+                        app_state.input_mode = ActiveInput::None;
+                    }
+                    ActiveInput::AddGroup => {
+                        if let Some(page) = app_state.selected_page {
+                            let title = app_state.buffer_string.clone();
+                            app_state.commit(Mutation::AddGroup { page, title });
+                        }
+
+                        app_state.mode = TodoModes::Normal;
+                        app_state.input_mode = ActiveInput::None;
+                    }
+                    ActiveInput::RenamePage => {
+                        if let Some(page) = app_state.selected_page {
+                            let title = app_state.buffer_string.clone();
+                            app_state.commit(Mutation::RenamePage { page, title });
+                        }
+
+                        app_state.mode = TodoModes::PageSelect;
+                        app_state.input_mode = ActiveInput::None;
+                    }
+                    ActiveInput::RenameGroup => {
+                        if let (Some(page), Some(group)) = (app_state.selected_page, app_state.selected_group) {
+                            let title = app_state.buffer_string.clone();
+                            app_state.commit(Mutation::RenameGroup { page, group, title });
+                        }
+
+                        app_state.mode = TodoModes::Normal;
+                        app_state.input_mode = ActiveInput::None;
+                    }
+                    ActiveInput::RenameSearch => {
+                        if let Some(PageEntry::Search(search)) = app_state.selected_entry() {
+                            let title = app_state.buffer_string.clone();
+                            app_state.commit(Mutation::RenameSearch { search, title });
+                        }
+
+                        app_state.mode = TodoModes::PageSelect;
+                        app_state.input_mode = ActiveInput::None;
+                    }
+                    ActiveInput::RenameTodo => {
+                        if let (Some(page), Some(group), Some(todo)) =
+                            (app_state.selected_page, app_state.selected_group, app_state.selected_todo) {
+                            let title = app_state.buffer_string.clone();
+                            app_state.commit(Mutation::RenameTodo { page, group, todo, title });
+                        }
+
+                        app_state.mode = TodoModes::Normal;
+                        app_state.input_mode = ActiveInput::None;
+                    }
+                    ActiveInput::Capture => {
+                        app_state.commit(Mutation::Capture { title: app_state.buffer_string.clone() });
+                        app_state.status_message = "Captured to the Inbox".to_string();
 
-    let menu = Block::default()
-        .borders(Borders::ALL)
-        .title(app_state.title.clone())
-        .title_alignment(Alignment::Center);
+                        app_state.mode = app_state.popup_return;
+                        app_state.input_mode = ActiveInput::None;
+                    }
+                    _ => (),
+                }
 
-    if app_state.page_list.len() > 0 {
+                app_state.buffer_string.clear();
+            }
+        }
+        _ => ()
+    }
+}
+
+fn handle_alert_box(_key: KeyEvent, app_state: &mut ApplicationState) { // Any key dismisses
+    app_state.mode = app_state.popup_return;
+    app_state.input_mode = ActiveInput::None;
+    app_state.has_popup = false;
+}
 
-        let list = List::new(app_state.page_list
-            .iter().map(|i| ListItem::new(Line::from(i.title.as_str()).alignment(Alignment::Center))))
-            .block(menu)
-            .highlight_symbol(">>")
-            .highlight_style(
-                Style::default()
-                    .fg(Color::Black)
-                    .bg(Color::Green),
-            );
+fn handle_confirm_box(key: KeyEvent, app_state: &mut ApplicationState) {
+    let accepted = match key.code {
+        KeyCode::Char('y') | KeyCode::Enter => true,
+        KeyCode::Char('n') | KeyCode::Esc => false,
+        _ => return,
+    };
 
-        frame.render_stateful_widget(list, area, &mut app_state.page_list_state);
+    app_state.mode = app_state.popup_return;
+    let action = std::mem::replace(&mut app_state.confirm_action, ConfirmAction::None);
+    if let ConfirmAction::RestoreRecovery(data) = action {
+        if accepted {
+            app_state.restore_recovery(data);
+        }
+        else if let Err(e) = app_state.storage.clear_recovery() {
+            app_state.alert_box(AlertMode::Error, format!("Could not remove recovery file: {}", e));
+        }
+        return;
     }
-    else {
-        let text = Paragraph::new(Text::from(vec![
-                Line::from("No pages found..."),
-                Line::from("Press \'a\' to create one."),
-        ])).block(menu);
 
-        frame.render_widget(text, area);
+    if !accepted {
+        return;
     }
-}
 
-fn render_popup_input_field(frame: &mut Frame, app_state: &mut ApplicationState, title: &str) {
-    let width = 50;
-    let height = 3;
-    
-    let rect = Rect::new((frame.area().width - width) / 2, (frame.area().height - height) / 2, width, height);
-    let textbox = Block::default().borders(Borders::ALL).title(title);
-    let buffer = Paragraph::new(app_state.buffer_string.clone()).block(textbox);
-    
-    frame.render_widget(buffer, rect); 
+    match action {
+        ConfirmAction::DeletePage(page) => {
+            app_state.commit(Mutation::DeletePage { page });
+            app_state.clamp_page_selection();
+        }
+        ConfirmAction::DeleteGroup(page, group) => app_state.commit(Mutation::DeleteGroup { page, group }),
+        ConfirmAction::DeleteTodo(page, group, todo) => app_state.commit(Mutation::DeleteTodo { page, group, todo }),
+        ConfirmAction::ClearGroup(page, group) => app_state.commit(Mutation::ClearGroup { page, group }),
+        ConfirmAction::PurgeTrash(entry) => {
+            app_state.commit(Mutation::PurgeTrash { entry });
+            clamp_trash_selection(app_state);
+        }
+        ConfirmAction::EmptyTrash => {
+            app_state.commit(Mutation::EmptyTrash);
+            clamp_trash_selection(app_state);
+        }
+        ConfirmAction::RestoreRecovery(_) | ConfirmAction::None => (),
+    }
 }
 
-fn render_alert_box(frame: &mut Frame, alert_mode: &AlertMode, message: &str) {
-    let width = 50;
-    let height = 7;
+fn handle_trash_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    let len = app_state.data.trash.len();
+    // The list is shown newest first
+    let entry = app_state.trash_list_state.selected()
+        .filter(|i| *i < len)
+        .map(|i| len - 1 - i);
 
-    let rect = Rect::new((frame.area().width - width) / 2, (frame.area().height - height) / 2, width, height);
+    match key.code {
+        KeyCode::Esc => app_state.mode = TodoModes::PageSelect,
+        KeyCode::Char('k') | KeyCode::Up if len > 0 => {
+            let up = match app_state.trash_list_state.selected() {
+                Some(up) => if up == 0 { len - 1 } else { up - 1 },
+                None => 0,
+            };
+            app_state.trash_list_state.select(Some(up));
+        }
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            let down = match app_state.trash_list_state.selected() {
+                Some(down) => if down >= len - 1 { 0 } else { down + 1 },
+                None => 0,
+            };
+            app_state.trash_list_state.select(Some(down));
+        }
+        KeyCode::Char('r') | KeyCode::Enter => {
+            let Some(entry) = entry else { return };
+            if !app_state.data.trash[entry].can_restore(&app_state.data.page_list) {
+                app_state.alert_box(AlertMode::Error,
+                    "Its page or group no longer exists.\nRestore that first.".to_string());
+                return;
+            }
 
-    let _title = match alert_mode {
-        AlertMode::Message => "Message...",
-        AlertMode::Warning => "Warning!",
-        AlertMode::Error => "Error!",
-        _ => "None",
-    };
-    let block = Block::default().borders(Borders::ALL).title(_title);
-    let paragraph = Paragraph::new(message).block(block);
-    frame.render_widget(paragraph, rect);
+            app_state.commit(Mutation::RestoreTrash { entry });
+            clamp_trash_selection(app_state);
+            app_state.clamp_page_selection();
+        }
+        KeyCode::Char('x') => {
+            if let Some(entry) = entry {
+                let message = format!("Permanently delete\n{}?", app_state.data.trash[entry].label());
+                app_state.confirm_box(ConfirmAction::PurgeTrash(entry), message);
+            }
+        }
+        KeyCode::Char('X') if len > 0 => {
+            let message = format!("Permanently delete all {} item(s) in the trash?", len);
+            app_state.confirm_box(ConfirmAction::EmptyTrash, message);
+        }
+        _ => (),
+    }
 }
 
-// ----------------------------- END OF RENDER -----------------------------
+fn handle_archive_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    let entries = app_state.archive_entries();
+    let len = entries.len();
+    let entry = app_state.archive_list_state.selected().and_then(|i| entries.get(i).copied());
 
-// --------------------------------- INPUT ---------------------------------
+    match key.code {
+        KeyCode::Esc if app_state.archive_page.is_some() => app_state.mode = TodoModes::Normal,
+        KeyCode::Esc => app_state.mode = TodoModes::PageSelect,
+        KeyCode::Char('k') | KeyCode::Up if len > 0 => {
+            let up = match app_state.archive_list_state.selected() {
+                Some(up) => if up == 0 { len - 1 } else { up - 1 },
+                None => 0,
+            };
+            app_state.archive_list_state.select(Some(up));
+        }
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            let down = match app_state.archive_list_state.selected() {
+                Some(down) => if down >= len - 1 { 0 } else { down + 1 },
+                None => 0,
+            };
+            app_state.archive_list_state.select(Some(down));
+        }
+        KeyCode::Char('r') | KeyCode::Enter => {
+            let Some((page, entry)) = entry else { return };
+            let p = &app_state.data.page_list[page];
+            if !p.archive[entry].can_restore(p) {
+                app_state.alert_box(AlertMode::Error,
+                    "Its group no longer exists.\nRestore or recreate it first.".to_string());
+                return;
+            }
 
-fn handle_input(key: KeyEvent, app_state: & mut ApplicationState) { // Routes input 
-    match app_state.mode {
-        TodoModes::Normal => handle_normal_input(key, app_state),
-        TodoModes::PageSelect => handle_page_select_input(key, app_state),
-        TodoModes::Insert => handle_insert(key, app_state),
-        TodoModes::AddSelect => handle_add_select_input(key, app_state),
-        TodoModes::Popup => handle_alert_box(key, app_state),
+            app_state.commit(Mutation::RestoreArchived { page, entry });
+            let len = app_state.archive_entries().len();
+            let selected = app_state.archive_list_state.selected().unwrap_or(0);
+            app_state.archive_list_state.select(if len == 0 { None } else { Some(selected.min(len - 1)) });
+        }
+        KeyCode::Char('a') => {
+            app_state.buffer_string = app_state.data.auto_archive_days.map_or(String::new(), |d| d.to_string());
+            app_state.mode = TodoModes::Insert;
+            app_state.input_mode = ActiveInput::AutoArchiveDays;
+        }
         _ => (),
     }
 }
 
-fn handle_page_select_input(key: KeyEvent, app_state: &mut ApplicationState) {
+fn clamp_trash_selection(app_state: &mut ApplicationState) {
+    let len = app_state.data.trash.len();
+    let selected = app_state.trash_list_state.selected().unwrap_or(0);
+    app_state.trash_list_state.select(if len == 0 { None } else { Some(selected.min(len - 1)) });
+}
+
+fn handle_finder_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    let len = app_state.finder_results().len();
+
     match key.code {
-        KeyCode::Esc => app_state.should_quit = true,
-        KeyCode::Char('k') | KeyCode::Up => { 
-            if app_state.page_list.len() > 0 {
-                let up = match app_state.page_list_state.selected() {
-                    Some(up) => {
-                        if up == 0 {app_state.page_list.len() - 1} else {up - 1}
-                    },
-                    None => 0,
-                };
-                app_state.page_list_state.select(Some(up));
-                app_state.selected_page = Some(up);
-            }
+        KeyCode::Esc => app_state.mode = app_state.finder_return,
+        KeyCode::Up if len > 0 => {
+            let up = match app_state.finder_list_state.selected() {
+                Some(up) => if up == 0 { len - 1 } else { up - 1 },
+                None => 0,
+            };
+            app_state.finder_list_state.select(Some(up));
         }
-        KeyCode::Char('j') | KeyCode::Down => {
-            if app_state.page_list.len() > 0 {
-                let down = match app_state.page_list_state.selected() {
-                    Some(down) => { 
-                        if down == app_state.page_list.len() - 1 {0} else {down + 1}
-                    },
-                    None => 0,
-                };
-                app_state.page_list_state.select(Some(down));
-                app_state.selected_page = Some(down);
-            }
+        KeyCode::Down if len > 0 => {
+            let down = match app_state.finder_list_state.selected() {
+                Some(down) => if down >= len - 1 { 0 } else { down + 1 },
+                None => 0,
+            };
+            app_state.finder_list_state.select(Some(down));
         }
         KeyCode::Enter => {
-            if !app_state.page_list_state.selected().is_none() && app_state.page_list.len() > 0 {
-                app_state.mode = TodoModes::Normal;
+            let results = app_state.finder_results();
+            if let Some(&(target, _)) = app_state.finder_list_state.selected().and_then(|i| results.get(i)) {
+                app_state.go_to(target);
             }
         }
-        KeyCode::Char('a') => { // Add page
-            app_state.mode = TodoModes::Insert;
-            app_state.input_mode = ActiveInput::AddPage;
+        KeyCode::Char(c) => {
+            app_state.finder.push(c);
+            app_state.finder_list_state.select(Some(0)); // Best match first
+        }
+        KeyCode::Backspace => {
+            app_state.finder.pop();
+            app_state.finder_list_state.select(Some(0));
         }
         _ => (),
     }
 }
 
-fn handle_normal_input(key: KeyEvent, app_state: &mut ApplicationState) { // MAIN SHIT
+// Every keystroke moves the selection to the first match from where the search started
+fn handle_search_input(key: KeyEvent, app_state: &mut ApplicationState) {
     match key.code {
-        KeyCode::Esc => app_state.mode = TodoModes::PageSelect,
-        KeyCode::Char('a') => app_state.mode = TodoModes::AddSelect,
-        KeyCode::Char('k') | KeyCode::Up => {
-            app_state.selected_item_up();
+        KeyCode::Esc => {
+            app_state.search.clear();
+            app_state.item_list_state.select(app_state.search_origin);
+            app_state.mode = TodoModes::Normal;
+            return;
         }
-        KeyCode::Char('j') | KeyCode::Down => {
-            app_state.selected_item_down();
+        KeyCode::Enter => {
+            if app_state.search_matches().is_empty() {
+                app_state.search.clear();
+            }
+            app_state.mode = TodoModes::Normal;
+            return;
         }
-        KeyCode::Char(' ') => {
-            if app_state.selected_item().is_none() && app_state.selected_group().is_some() {
-                app_state.selected_mut_group().unwrap().toggle_show_items();
-                return;
-            } 
+        KeyCode::Char(c) => app_state.search.push(c),
+        KeyCode::Backspace => {
+            app_state.search.pop();
+        }
+        _ => return,
+    }
 
-            if app_state.selected_mut_item().is_some() {
-                app_state.selected_mut_item().unwrap().toggle_state();
-            }
+    let origin = app_state.search_origin.unwrap_or(0);
+    if !app_state.jump_to_match(origin, true, true) {
+        app_state.item_list_state.select(app_state.search_origin);
+    }
+}
+
+fn handle_filter_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    let criteria = app_state.filter_criteria();
+    let len = criteria.len();
+
+    match key.code {
+        KeyCode::Esc => app_state.mode = TodoModes::Normal,
+        KeyCode::Char('k') | KeyCode::Up if len > 0 => {
+            let up = match app_state.filter_list_state.selected() {
+                Some(up) => if up == 0 { len - 1 } else { up - 1 },
+                None => 0,
+            };
+            app_state.filter_list_state.select(Some(up));
         }
-        KeyCode::Enter => {
-            
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            let down = match app_state.filter_list_state.selected() {
+                Some(down) => if down >= len - 1 { 0 } else { down + 1 },
+                None => 0,
+            };
+            app_state.filter_list_state.select(Some(down));
         }
+        KeyCode::Char(' ') | KeyCode::Enter => {
+            if let Some(criterion) = app_state.filter_list_state.selected().and_then(|i| criteria.get(i)) {
+                app_state.filter.toggle(criterion);
+            }
+        }
+        KeyCode::Char('c') => app_state.filter = Filter::default(),
         _ => (),
     }
 }
 
-fn handle_add_select_input(key: KeyEvent, app_state: &mut ApplicationState) {
+fn handle_blocker_picker_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    let candidates = app_state.blocker_candidates();
+    let len = candidates.len();
+
     match key.code {
-        KeyCode::Char('i') => {
-            app_state.mode = TodoModes::Insert;
-            app_state.input_mode = ActiveInput::AddTodo;
+        KeyCode::Esc => app_state.mode = TodoModes::Normal,
+        KeyCode::Char('k') | KeyCode::Up if len > 0 => {
+            let up = match app_state.blocker_list_state.selected() {
+                Some(up) => if up == 0 { len - 1 } else { up - 1 },
+                None => 0,
+            };
+            app_state.blocker_list_state.select(Some(up));
         }
-        KeyCode::Char('g') => { 
-            app_state.mode = TodoModes::Insert; 
-            app_state.input_mode = ActiveInput::AddGroup;
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            let down = match app_state.blocker_list_state.selected() {
+                Some(down) => if down >= len - 1 { 0 } else { down + 1 },
+                None => 0,
+            };
+            app_state.blocker_list_state.select(Some(down));
         }
-        KeyCode::Esc => {
-            app_state.mode = TodoModes::Normal;
-            app_state.input_mode = ActiveInput::None;
+        KeyCode::Char(' ') | KeyCode::Enter => {
+            let (Some(page), Some(group), Some(todo), Some(item)) = (app_state.selected_page,
+                app_state.selected_group, app_state.selected_todo, app_state.selected_item()) else {
+                app_state.mode = TodoModes::Normal;
+                return;
+            };
+            let Some(&(blocker, _)) = app_state.blocker_list_state.selected().and_then(|i| candidates.get(i)) else {
+                return;
+            };
+
+            if !item.blocked_by.contains(&blocker) && app_state.data.waits_on(blocker, item.id) {
+                app_state.alert_box(AlertMode::Warning,
+                    "That todo is already waiting on this one;\nthey would block each other forever.".to_string());
+                return;
+            }
+            app_state.commit(Mutation::ToggleBlocker { page, group, todo, blocker });
         }
         _ => (),
     }
 }
 
-fn handle_insert(key: KeyEvent, app_state: &mut ApplicationState) {
+fn handle_edit_form(key: KeyEvent, app_state: &mut ApplicationState) {
+    let Some(form) = app_state.edit_form.as_mut() else {
+        app_state.mode = TodoModes::Normal;
+        return;
+    };
+
     match key.code {
+        KeyCode::Esc => { // Discard
+            app_state.edit_form = None;
+            app_state.mode = TodoModes::Normal;
+        }
+        KeyCode::Tab | KeyCode::Down => form.focus = form.focus.next(),
+        KeyCode::BackTab | KeyCode::Up => form.focus = form.focus.prev(),
+        KeyCode::Left if form.focus == EditField::Status => form.cycle_status(false),
+        KeyCode::Right | KeyCode::Char(' ') if form.focus == EditField::Status => form.cycle_status(true),
+        KeyCode::Left if form.focus == EditField::Priority => form.cycle_priority(false),
+        KeyCode::Right | KeyCode::Char(' ') if form.focus == EditField::Priority => form.cycle_priority(true),
         KeyCode::Char(c) => {
-            app_state.buffer_string.push(c);
+            if let Some(text) = form.focused_text() {
+                text.push(c);
+            }
         }
         KeyCode::Backspace => {
-            app_state.buffer_string.pop();
-        }
-        KeyCode::Esc => { // Cancel
-            app_state.buffer_string.clear();
-            match app_state.input_mode {
-                ActiveInput::AddPage => {
-                    app_state.mode = TodoModes::PageSelect;
-                    app_state.input_mode = ActiveInput::None;
-                }
-                ActiveInput::AddGroup | ActiveInput::AddTodo => {
-                    app_state.mode = TodoModes::Normal;
-                    app_state.input_mode = ActiveInput::None;
-                }
-                _ => (),
+            if let Some(text) = form.focused_text() {
+                text.pop();
             }
         }
-        KeyCode::Enter => {
-            app_state.buffer_string.trim();
-            if !app_state.buffer_string.is_empty() {
-                match app_state.input_mode {
-                    ActiveInput::AddPage => {
-                        app_state.add_page(app_state.buffer_string.clone());
+        KeyCode::Enter => match form.focus {
+            EditField::Save => save_edit_form(app_state),
+            EditField::Discard => {
+                app_state.edit_form = None;
+                app_state.mode = TodoModes::Normal;
+            }
+            _ => form.focus = form.focus.next(),
+        },
+        _ => (),
+    }
+}
 
-                        app_state.mode = TodoModes::PageSelect;
-                        app_state.input_mode = ActiveInput::None;
-                    }
-                    ActiveInput::AddGroup => {
-                        let group_title = app_state.buffer_string.clone();
-                        if let Some(page) = app_state.selected_mut_page() {
-                            page.add_group(group_title);
-                        }
+fn save_edit_form(app_state: &mut ApplicationState) {
+    let Some(mut form) = app_state.edit_form.take() else {
+        return;
+    };
 
-                        app_state.mode = TodoModes::Normal;
-                        app_state.input_mode = ActiveInput::None;
-                    }
-                    ActiveInput::AddTodo => {
-                        let todo_title = app_state.buffer_string.clone();
-                        if let Some(group) = app_state.selected_mut_group() {
-                            group.add_todo(todo_title);
-                            app_state.mode = TodoModes::Normal;
-                            app_state.input_mode = ActiveInput::None;
-                        }
-                        else {
-                            // TODO: have it create a new group "Untitled" and add todo on it. 
-                            app_state.alert_box(AlertMode::Error,
-                            "Please have a group selected/highlighted \n 
-                            to create a todo item".to_string());
-                        }
-                    }
-                    _ => (),
-                }
+    if !form.validate(Local::now().date_naive()) { // Keep the form open so the user can fix it
+        app_state.edit_form = Some(form);
+        return;
+    }
 
-                app_state.buffer_string.clear();
-            }
-        }
-        _ => ()
+    if let EditTarget::Existing { page, group, todo } = form.target
+        && let Some(before) = app_state.data.page_list.get(page)
+            .and_then(|p| p.group_list.get(group))
+            .and_then(|g| g.item_list.get(todo))
+        && form.item.state == TodoState::Done
+        && before.state != TodoState::Done
+        && !app_state.data.open_blockers(before).is_empty() {
+        form.error = Some("It's blocked by todos that aren't done yet".to_string());
+        form.focus = EditField::Status;
+        app_state.edit_form = Some(form);
+        return;
+    }
+
+    app_state.mode = TodoModes::Normal;
+    match form.target {
+        EditTarget::New { page, group } => app_state.commit(Mutation::AddTodo { page, group, item: form.item }),
+        EditTarget::Subtask { page, group, parent } => app_state.commit(Mutation::AddSubtask { page, group, parent, item: form.item }),
+        EditTarget::Existing { page, group, todo } => app_state.commit(Mutation::EditTodo { page, group, todo, item: form.item }),
     }
 }
 
-fn handle_alert_box(key: KeyEvent, app_state: &mut ApplicationState) {
+fn handle_move_to_input(key: KeyEvent, app_state: &mut ApplicationState) {
+    let destinations = app_state.move_destinations();
+    let len = destinations.len();
+
     match key.code {
-        _ => {
-            match app_state.input_mode {
-                ActiveInput::AddPage | ActiveInput::None => {
-                    app_state.mode = TodoModes::PageSelect;
-                    app_state.input_mode = ActiveInput::None;
-                    app_state.has_popup = false;
-                }
-                ActiveInput::AddGroup | ActiveInput::AddTodo => {
-                    app_state.mode = TodoModes::Normal;
-                    app_state.input_mode = ActiveInput::None;
-                    app_state.has_popup = false;
-                }
+        KeyCode::Esc => app_state.mode = TodoModes::Normal,
+        KeyCode::Char('k') | KeyCode::Up if len > 0 => {
+            let up = match app_state.move_list_state.selected() {
+                Some(up) => if up == 0 { len - 1 } else { up - 1 },
+                None => 0,
+            };
+            app_state.move_list_state.select(Some(up));
+        }
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            let down = match app_state.move_list_state.selected() {
+                Some(down) => if down >= len - 1 { 0 } else { down + 1 },
+                None => 0,
+            };
+            app_state.move_list_state.select(Some(down));
+        }
+        KeyCode::Enter => {
+            let (Some(page), Some(group), Some(todo)) =
+                (app_state.selected_page, app_state.selected_group, app_state.selected_todo) else {
+                app_state.mode = TodoModes::Normal;
+                return;
+            };
+            let Some(&(to_page, to_group, _)) = app_state.move_list_state.selected()
+                .and_then(|i| destinations.get(i)) else {
+                return;
+            };
+
+            let id = app_state.selected_item().map(|t| t.id);
+            app_state.mode = TodoModes::Normal;
+            app_state.commit(Mutation::MoveTodoTo { page, group, todo, to_page, to_group });
+
+            // Follow the todo to where it landed; its subtasks came along after it
+            app_state.select_page(to_page);
+            let Some(target) = app_state.data.page_list[to_page].group_list.get(to_group) else {
+                return;
+            };
+            if !target.show_items { // Out of undo, so one [u] takes the move back
+                app_state.housekeep(Mutation::ToggleShowItems { page: to_page, group: to_group }, Local::now().naive_local());
             }
+            app_state.select_todo_by_id(to_group, id);
         }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2025-06-01 12:00", "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn item(title: &str, depth: usize) -> TodoItem {
+        let mut item = TodoItem::new(title.to_string());
+        item.depth = depth;
+        item
+    }
+
+    // A group from (title, depth) pairs, in list order
+    fn group(items: &[(&str, usize)]) -> TodoGroup {
+        let mut group = TodoGroup::new("G".to_string());
+        group.item_list = items.iter().map(|&(title, depth)| item(title, depth)).collect();
+        group
+    }
+
+    fn shape(items: &[TodoItem]) -> Vec<(&str, usize)> {
+        items.iter().map(|t| (t.title.as_str(), t.depth)).collect()
+    }
+
+    #[test]
+    fn indent_takes_the_subtree_along() {
+        let mut g = group(&[("A", 0), ("B", 0), ("b1", 1), ("C", 0)]);
+        g.item_list[0].folded = true;
+        assert!(g.indent(0).is_none());
+        g.indent(1).unwrap();
+        assert_eq!(shape(&g.item_list), [("A", 0), ("B", 1), ("b1", 2), ("C", 0)]);
+        assert!(!g.item_list[0].folded);
+    }
+
+    #[test]
+    fn outdent_lands_after_the_parent_subtree() {
+        let mut g = group(&[("A", 0), ("a1", 1), ("x", 2), ("a2", 1), ("B", 0)]);
+        assert!(g.outdent(0).is_none());
+        g.outdent(1).unwrap();
+        assert_eq!(shape(&g.item_list), [("A", 0), ("a2", 1), ("a1", 0), ("x", 1), ("B", 0)]);
+    }
+
+    #[test]
+    fn moves_swap_whole_subtrees_among_siblings() {
+        let mut g = group(&[("A", 0), ("a1", 1), ("B", 0), ("b1", 1), ("b2", 1)]);
+        g.move_todo_down(0).unwrap();
+        assert_eq!(shape(&g.item_list), [("B", 0), ("b1", 1), ("b2", 1), ("A", 0), ("a1", 1)]);
+        assert!(g.move_todo_down(3).is_none());
+        assert!(g.move_todo_up(0).is_none());
+        assert!(g.move_todo_up(1).is_none());
+        g.move_todo_up(2).unwrap();
+        assert_eq!(shape(&g.item_list), [("B", 0), ("b2", 1), ("b1", 1), ("A", 0), ("a1", 1)]);
+        assert!(g.move_todo_down(4).is_none());
+    }
+
+    #[test]
+    fn subtrees_come_out_and_go_back_in() {
+        let mut g = group(&[("A", 0), ("a1", 1), ("x", 2), ("B", 0)]);
+        let subtree = g.remove_subtree(1).unwrap();
+        assert_eq!(shape(&subtree), [("a1", 1), ("x", 2)]);
+        assert_eq!(shape(&g.item_list), [("A", 0), ("B", 0)]);
+        assert!(g.remove_subtree(2).is_none());
+
+        // Only as deep as the todo above allows
+        g.insert_subtree(2, subtree, 5);
+        assert_eq!(shape(&g.item_list), [("A", 0), ("B", 0), ("a1", 1), ("x", 2)]);
+    }
+
+    #[test]
+    fn insert_subtree_skips_a_subtree_at_its_spot() {
+        let mut g = group(&[("A", 0), ("a1", 1), ("B", 0)]);
+        g.insert_subtree(1, vec![item("X", 3), item("x", 4)], 0);
+        assert_eq!(shape(&g.item_list), [("A", 0), ("a1", 1), ("X", 0), ("x", 1), ("B", 0)]);
+    }
+
+    #[test]
+    fn add_subtask_checks_the_parent() {
+        let mut g = group(&[("A", 0), ("a1", 1)]);
+        assert!(g.add_subtask(2, item("y", 0)).is_none());
+        g.add_subtask(0, item("a2", 0)).unwrap();
+        assert_eq!(shape(&g.item_list), [("A", 0), ("a1", 1), ("a2", 1)]);
+    }
+
+    #[test]
+    fn sorting_keeps_subtasks_under_their_parent() {
+        let mut g = group(&[("A", 0), ("a1", 1), ("a2", 1), ("B", 0), ("b1", 1)]);
+        g.item_list[2].priority = Some(Priority::P1);
+        g.item_list[3].priority = Some(Priority::P2);
+        let subtrees = split_subtrees(g.item_list.clone());
+        assert_eq!(subtrees.iter().map(|s| s.len()).collect::<Vec<_>>(), [3, 2]);
+
+        g.sort_by_priority();
+        assert_eq!(shape(&g.item_list), [("B", 0), ("b1", 1), ("A", 0), ("a2", 1), ("a1", 1)]);
+    }
+
+    #[test]
+    fn trashed_todo_comes_back_past_new_subtasks() {
+        let mut page = TodoPage::new("P".to_string());
+        page.add_group(group(&[("A", 0), ("X", 0), ("B", 0)]));
+        let mut pages = vec![page];
+        let g = &mut pages[0].group_list[0];
+        let removed = g.remove_subtree(1).unwrap();
+        g.add_subtask(0, item("a1", 0)).unwrap();
+
+        let entry = TrashEntry::Todo {
+            page: 0,
+            page_title: "P".to_string(),
+            group: 0,
+            group_title: "G".to_string(),
+            index: 1,
+            item: Box::new(removed[0].clone()),
+            children: Vec::new(),
+        };
+        entry.restore(&mut pages).unwrap();
+        assert_eq!(shape(&pages[0].group_list[0].item_list), [("A", 0), ("a1", 1), ("X", 0), ("B", 0)]);
+    }
+
+    #[test]
+    fn archived_todo_comes_back_as_a_top_level_subtree() {
+        let mut page = TodoPage::new("P".to_string());
+        page.add_group(group(&[("B", 0), ("b1", 1)]));
+        let entry = ArchivedTodo {
+            group: page.group_list[0].id,
+            group_title: "G".to_string(),
+            index: 1,
+            archived: at(),
+            item: item("A", 0),
+            children: vec![item("a1", 1)],
+        };
+        entry.restore(&mut page, at()).unwrap();
+        assert_eq!(shape(&page.group_list[0].item_list), [("B", 0), ("b1", 1), ("A", 0), ("a1", 1)]);
     }
 }
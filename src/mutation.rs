@@ -0,0 +1,457 @@
+use std::collections::BTreeSet;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::{activity::Change, archive, query::SavedSearch, Priority, ViewSettings, TodoData, TodoState, TodoGroup, TodoItem, TodoPage, split_subtrees, trash::TrashEntry};
+
+// Every change to the page list goes through a Mutation so it can be journaled
+// and replayed after a crash.
+#[derive(Serialize, Deserialize)]
+pub enum Mutation {
+    AddPage { title: String },
+    AddGroup { page: usize, title: String },
+    AddTodo { page: usize, group: usize, item: TodoItem },
+    AddSubtask { page: usize, group: usize, parent: usize, item: TodoItem },
+    EditTodo { page: usize, group: usize, todo: usize, item: TodoItem },
+    RenamePage { page: usize, title: String },
+    RenameGroup { page: usize, group: usize, title: String },
+    RenameTodo { page: usize, group: usize, todo: usize, title: String },
+    ToggleShowItems { page: usize, group: usize },
+    ToggleState { page: usize, group: usize, todo: usize },
+    SetState { page: usize, group: usize, todo: usize, state: TodoState, reason: Option<String> },
+    SetPriority { page: usize, group: usize, todo: usize, priority: Option<Priority> },
+    SortGroup { page: usize, group: usize },
+    SetTags { page: usize, group: usize, todo: usize, tags: BTreeSet<String> },
+    MoveTodo { page: usize, group: usize, todo: usize, up: bool },
+    IndentTodo { page: usize, group: usize, todo: usize, outdent: bool },
+    FoldTodo { page: usize, group: usize, todo: usize },
+    ToggleAutoComplete { page: usize, group: usize, todo: usize },
+    ToggleBlocker { page: usize, group: usize, todo: usize, blocker: u32 },
+    MoveGroup { page: usize, group: usize, up: bool },
+    MovePage { page: usize, up: bool },
+    MoveTodoTo { page: usize, group: usize, todo: usize, to_page: usize, to_group: usize },
+    DeletePage { page: usize },
+    DeleteGroup { page: usize, group: usize },
+    DeleteTodo { page: usize, group: usize, todo: usize },
+    ClearGroup { page: usize, group: usize },
+    RestoreTrash { entry: usize },
+    PurgeTrash { entry: usize },
+    EmptyTrash,
+    ArchiveDone { page: usize, group: usize },
+    RestoreArchived { page: usize, entry: usize },
+    SetAutoArchive { days: Option<u32> },
+    SetView { page: usize, view: ViewSettings },
+    AddSearch { query: String },
+    EditSearch { search: usize, query: String },
+    RenameSearch { search: usize, title: String },
+    DeleteSearch { search: usize },
+    CollapseGroups { page: usize },
+    CreateInbox,
+    Capture { title: String },
+    UpdateLate,
+    AutoArchive,
+}
+
+// A journal line: the mutation and when it was made, so a replay stamps the same times
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(default)]
+    pub seq: u64, // Counts up across snapshots; 0 in journals from before it existed
+    pub at: NaiveDateTime,
+    pub mutation: Mutation,
+}
+
+impl Mutation {
+    // Short human description, e.g. for the undo message; call before applying
+    pub fn describe(&self, data: &TodoData) -> String {
+        let pages = &data.page_list;
+        match self {
+            Mutation::AddPage { title } => format!("add page \"{}\"", title),
+            Mutation::AddGroup { title, .. } => format!("add group \"{}\"", title),
+            Mutation::AddTodo { item, .. } => format!("add todo \"{}\"", item.title),
+            Mutation::AddSubtask { item, .. } => format!("add subtask \"{}\"", item.title),
+            Mutation::EditTodo { page, group, todo, .. } => format!("edit {}", item_name(pages, *page, *group, *todo)),
+            Mutation::RenamePage { page, .. } => format!("rename {}", page_name(pages, *page)),
+            Mutation::RenameGroup { page, group, .. } => format!("rename {}", group_name(pages, *page, *group)),
+            Mutation::RenameTodo { page, group, todo, .. } => format!("rename {}", item_name(pages, *page, *group, *todo)),
+            Mutation::ToggleShowItems { page, group } => format!("fold {}", group_name(pages, *page, *group)),
+            Mutation::ToggleState { page, group, todo, .. } => format!("toggle {}", item_name(pages, *page, *group, *todo)),
+            Mutation::SetState { page, group, todo, state, .. } => format!("mark {} {}",
+                item_name(pages, *page, *group, *todo), state.label().to_lowercase()),
+            Mutation::SetPriority { page, group, todo, .. } => format!("set priority of {}", item_name(pages, *page, *group, *todo)),
+            Mutation::SetTags { page, group, todo, .. } => format!("tag {}", item_name(pages, *page, *group, *todo)),
+            Mutation::SortGroup { page, group } => format!("sort {}", group_name(pages, *page, *group)),
+            Mutation::MoveTodo { page, group, todo, .. } => format!("move {}", item_name(pages, *page, *group, *todo)),
+            Mutation::IndentTodo { page, group, todo, .. } => format!("indent {}", item_name(pages, *page, *group, *todo)),
+            Mutation::FoldTodo { page, group, todo } => format!("fold {}", item_name(pages, *page, *group, *todo)),
+            Mutation::ToggleAutoComplete { page, group, todo } => format!("toggle auto-complete of {}",
+                item_name(pages, *page, *group, *todo)),
+            Mutation::ToggleBlocker { page, group, todo, .. } => format!("change blockers of {}",
+                item_name(pages, *page, *group, *todo)),
+            Mutation::MoveGroup { page, group, .. } => format!("move {}", group_name(pages, *page, *group)),
+            Mutation::MovePage { page, .. } => format!("move {}", page_name(pages, *page)),
+            Mutation::MoveTodoTo { page, group, todo, to_page, to_group } => format!("move {} to {}",
+                item_name(pages, *page, *group, *todo), group_name(pages, *to_page, *to_group)),
+            Mutation::DeletePage { page } => format!("delete {}", page_name(pages, *page)),
+            Mutation::DeleteGroup { page, group } => format!("delete {}", group_name(pages, *page, *group)),
+            Mutation::DeleteTodo { page, group, todo } => format!("delete {}", item_name(pages, *page, *group, *todo)),
+            Mutation::ClearGroup { page, group } => format!("clear {}", group_name(pages, *page, *group)),
+            Mutation::RestoreTrash { .. } => "restore from trash".to_string(),
+            Mutation::PurgeTrash { .. } => "purge from trash".to_string(),
+            Mutation::EmptyTrash => "empty trash".to_string(),
+            Mutation::ArchiveDone { page, group } => format!("archive done todos of {}", group_name(pages, *page, *group)),
+            Mutation::RestoreArchived { .. } => "restore from archive".to_string(),
+            Mutation::SetAutoArchive { days: Some(days) } => format!("auto-archive after {} day(s)", days),
+            Mutation::SetAutoArchive { days: None } => "turn off auto-archive".to_string(),
+            Mutation::SetView { page, .. } => format!("change view of {}", page_name(pages, *page)),
+            Mutation::AddSearch { query } => format!("save search \"{}\"", query),
+            Mutation::EditSearch { search, .. } => format!("edit {}", search_name(data, *search)),
+            Mutation::RenameSearch { search, .. } => format!("rename {}", search_name(data, *search)),
+            Mutation::DeleteSearch { search } => format!("delete {}", search_name(data, *search)),
+            Mutation::CollapseGroups { page } => format!("collapse groups of {}", page_name(pages, *page)),
+            Mutation::CreateInbox => "create the inbox".to_string(),
+            Mutation::Capture { title } => format!("capture \"{}\"", title),
+            Mutation::UpdateLate => "update late todos".to_string(),
+            Mutation::AutoArchive => "auto-archive old todos".to_string(),
+        }
+    }
+
+    // None when the target no longer exists; `now` stamps the todos' activity logs
+    pub fn apply(&self, data: &mut TodoData, now: NaiveDateTime) -> Option<()> {
+        let pages = &mut data.page_list;
+        match self {
+            Mutation::AddPage { title } => {
+                let mut new_page = TodoPage::new(title.clone());
+                new_page.id = next_id(&mut data.id_counter);
+                pages.push(new_page);
+            }
+            Mutation::AddGroup { page, title } => {
+                let p = pages.get_mut(*page)?;
+                let mut new_group = TodoGroup::new(title.clone());
+                new_group.id = next_id(&mut data.id_counter);
+                p.add_group(new_group);
+            }
+            Mutation::AddTodo { page, group, item } => {
+                let g = group_mut(pages, *page, *group)?;
+                let mut new_item = item.clone();
+                new_item.id = next_id(&mut data.id_counter);
+                new_item.created = Some(now);
+                new_item.log(now, Change::Created);
+                g.add_todo(new_item);
+            }
+            Mutation::AddSubtask { page, group, parent, item } => {
+                let g = group_mut(pages, *page, *group)?;
+                // Before taking an id, so a stale parent doesn't burn one
+                g.item_list.get(*parent)?;
+                let mut new_item = item.clone();
+                new_item.id = next_id(&mut data.id_counter);
+                new_item.created = Some(now);
+                new_item.log(now, Change::Created);
+                g.add_subtask(*parent, new_item)?;
+            }
+            Mutation::EditTodo { page, group, todo, item } => {
+                let unfinished = unfinished_ids(pages);
+                let g = group_mut(pages, *page, *group)?;
+                let current = g.item_list.get_mut(*todo)?;
+                current.rename(item.title.clone(), now);
+                if current.description != item.description || current.due != item.due
+                    || current.priority != item.priority || current.recurrence != item.recurrence {
+                    current.description = item.description.clone();
+                    current.due = item.due;
+                    current.priority = item.priority;
+                    current.recurrence = item.recurrence.clone();
+                    current.log(now, Change::Edited);
+                }
+                // Through set_state, so finishing it here counts like any other way
+                set_state(g, *todo, item.state, item.failed_reason.clone(), now, &mut data.id_counter, &unfinished)?;
+            }
+            Mutation::RenamePage { page, title } => {
+                pages.get_mut(*page)?.rename(title.clone());
+            }
+            Mutation::RenameGroup { page, group, title } => {
+                group_mut(pages, *page, *group)?.rename(title.clone());
+            }
+            Mutation::RenameTodo { page, group, todo, title } => {
+                item_mut(pages, *page, *group, *todo)?.rename(title.clone(), now);
+            }
+            Mutation::ToggleShowItems { page, group } => {
+                group_mut(pages, *page, *group)?.toggle_show_items();
+            }
+            Mutation::ToggleState { page, group, todo } => {
+                let unfinished = unfinished_ids(pages);
+                let g = group_mut(pages, *page, *group)?;
+                let state = g.item_list.get(*todo)?.toggled_state();
+                set_state(g, *todo, state, None, now, &mut data.id_counter, &unfinished)?;
+            }
+            Mutation::SetState { page, group, todo, state, reason } => {
+                let unfinished = unfinished_ids(pages);
+                let g = group_mut(pages, *page, *group)?;
+                set_state(g, *todo, *state, reason.clone(), now, &mut data.id_counter, &unfinished)?;
+            }
+            Mutation::SetPriority { page, group, todo, priority } => {
+                item_mut(pages, *page, *group, *todo)?.priority = *priority;
+            }
+            Mutation::SetTags { page, group, todo, tags } => {
+                item_mut(pages, *page, *group, *todo)?.tags = tags.clone();
+            }
+            Mutation::SortGroup { page, group } => {
+                group_mut(pages, *page, *group)?.sort_by_priority();
+            }
+            Mutation::MoveTodo { page, group, todo, up } => {
+                let g = group_mut(pages, *page, *group)?;
+                if *up { g.move_todo_up(*todo)?; } else { g.move_todo_down(*todo)?; }
+            }
+            Mutation::IndentTodo { page, group, todo, outdent } => {
+                let g = group_mut(pages, *page, *group)?;
+                if *outdent { g.outdent(*todo)?; } else { g.indent(*todo)?; }
+            }
+            Mutation::FoldTodo { page, group, todo } => {
+                group_mut(pages, *page, *group)?.toggle_fold(*todo)?;
+            }
+            Mutation::ToggleAutoComplete { page, group, todo } => {
+                let g = group_mut(pages, *page, *group)?;
+                let item = g.item_list.get_mut(*todo)?;
+                item.auto_complete = !item.auto_complete;
+            }
+            Mutation::ToggleBlocker { page, group, todo, blocker } => {
+                let item = item_mut(pages, *page, *group, *todo)?;
+                if !item.blocked_by.remove(blocker) {
+                    item.blocked_by.insert(*blocker);
+                }
+            }
+            Mutation::MoveGroup { page, group, up } => {
+                let p = pages.get_mut(*page)?;
+                if *up { p.move_group_up(*group)?; } else { p.move_group_down(*group)?; }
+            }
+            Mutation::MovePage { page, up } => {
+                let step = |p: usize| if *up { p.checked_sub(1) } else { Some(p + 1) };
+                let mut other = step(*page)?;
+                // The inbox isn't in the page list, so hop over it
+                if other < pages.len() && Some(pages[other].id) == data.inbox {
+                    other = step(other)?;
+                }
+                if other >= pages.len() || *page >= pages.len() {
+                    return None;
+                }
+                let moved = pages.remove(*page);
+                pages.insert(other, moved);
+            }
+            Mutation::MoveTodoTo { page, group, todo, to_page, to_group } => {
+                let target = pages.get(*to_page)?;
+                let to = format!("{} › {}", target.title, target.group_list.get(*to_group)?.title);
+                let subtree = group_mut(pages, *page, *group)?.remove_subtree(*todo)?;
+                let target = group_mut(pages, *to_page, *to_group)?;
+                let moved = target.item_list.len();
+                target.insert_subtree(moved, subtree, 0);
+                target.item_list[moved].log(now, Change::Moved { to });
+            }
+            Mutation::DeletePage { page } => {
+                pages.get(*page)?;
+                data.trash.push(TrashEntry::Page { index: *page, page: pages.remove(*page) });
+            }
+            Mutation::DeleteGroup { page, group } => {
+                let p = pages.get_mut(*page)?;
+                let removed = p.remove_group(*group)?;
+                data.trash.push(TrashEntry::Group {
+                    page: *page,
+                    page_title: p.title.clone(),
+                    index: *group,
+                    group: removed,
+                });
+            }
+            Mutation::DeleteTodo { page, group, todo } => {
+                let page_title = pages.get(*page)?.title.clone();
+                let g = group_mut(pages, *page, *group)?;
+                let mut removed = g.remove_subtree(*todo)?;
+                let children = removed.split_off(1);
+                data.trash.push(TrashEntry::Todo {
+                    page: *page,
+                    page_title,
+                    group: *group,
+                    group_title: g.title.clone(),
+                    index: *todo,
+                    item: Box::new(removed.pop()?),
+                    children,
+                });
+            }
+            Mutation::ClearGroup { page, group } => {
+                let page_title = pages.get(*page)?.title.clone();
+                let g = group_mut(pages, *page, *group)?;
+                let group_title = g.title.clone();
+                let mut index = 0;
+                for mut subtree in split_subtrees(g.clear_list()) {
+                    let children = subtree.split_off(1);
+                    let len = 1 + children.len();
+                    data.trash.push(TrashEntry::Todo {
+                        page: *page,
+                        page_title: page_title.clone(),
+                        group: *group,
+                        group_title: group_title.clone(),
+                        index,
+                        item: Box::new(subtree.pop()?),
+                        children,
+                    });
+                    index += len;
+                }
+            }
+            Mutation::RestoreTrash { entry } => {
+                if !data.trash.get(*entry)?.can_restore(pages) {
+                    return None;
+                }
+                data.trash.remove(*entry).restore(pages)?;
+            }
+            Mutation::PurgeTrash { entry } => {
+                data.trash.get(*entry)?;
+                data.trash.remove(*entry);
+            }
+            Mutation::EmptyTrash => data.trash.clear(),
+            Mutation::ArchiveDone { page, group } => {
+                if archive::archive_done(pages.get_mut(*page)?, *group, now, None)? == 0 {
+                    return None;
+                }
+            }
+            Mutation::RestoreArchived { page, entry } => {
+                let p = pages.get_mut(*page)?;
+                if !p.archive.get(*entry)?.can_restore(p) {
+                    return None;
+                }
+                p.archive.remove(*entry).restore(p, now)?;
+            }
+            Mutation::SetAutoArchive { days } => data.auto_archive_days = *days,
+            Mutation::SetView { page, view } => pages.get_mut(*page)?.view = view.clone(),
+            // Named after the query until it's renamed
+            Mutation::AddSearch { query } => data.searches.push(SavedSearch { title: query.clone(), query: query.clone() }),
+            Mutation::EditSearch { search, query } => data.searches.get_mut(*search)?.query = query.clone(),
+            Mutation::RenameSearch { search, title } => data.searches.get_mut(*search)?.title = title.clone(),
+            Mutation::DeleteSearch { search } => {
+                data.searches.get(*search)?;
+                data.searches.remove(*search);
+            }
+            Mutation::CollapseGroups { page } => {
+                let groups = &mut pages.get_mut(*page)?.group_list;
+                if groups.iter().all(|g| !g.show_items) {
+                    return None;
+                }
+                groups.iter_mut().for_each(|g| g.show_items = false);
+            }
+            Mutation::CreateInbox => {
+                if data.inbox_index().is_some() {
+                    return None;
+                }
+                inbox_group(data);
+            }
+            Mutation::Capture { title } => {
+                let mut item = TodoItem::new(title.clone());
+                item.id = next_id(&mut data.id_counter);
+                item.created = Some(now);
+                item.log(now, Change::Created);
+                inbox_group(data).add_todo(item);
+            }
+            Mutation::UpdateLate => {
+                let mut changed = false;
+                for item in pages.iter_mut().flat_map(|p| &mut p.group_list).flat_map(|g| &mut g.item_list) {
+                    changed |= item.update_late(now);
+                }
+                if !changed {
+                    return None;
+                }
+            }
+            Mutation::AutoArchive => {
+                let age = TimeDelta::try_days(data.auto_archive_days?.into())?;
+                let before = now.checked_sub_signed(age)?;
+                let mut archived = 0;
+                for page in pages.iter_mut() {
+                    for group in 0..page.group_list.len() {
+                        archived += archive::archive_done(page, group, now, Some(before))?;
+                    }
+                }
+                if archived == 0 {
+                    return None;
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+// Ids are handed out while applying, so replaying the journal reproduces them
+fn next_id(counter: &mut u32) -> u32 {
+    *counter += 1;
+    *counter
+}
+
+// Where quick capture goes: the inbox page's first group, making either if it's missing
+fn inbox_group(data: &mut TodoData) -> &mut TodoGroup {
+    let p = match data.inbox_index() {
+        Some(p) => p,
+        None => {
+            let mut page = TodoPage::new("Inbox".to_string());
+            page.id = next_id(&mut data.id_counter);
+            data.inbox = Some(page.id);
+            data.page_list.push(page);
+            data.page_list.len() - 1
+        }
+    };
+    let page = &mut data.page_list[p];
+    if page.group_list.is_empty() {
+        let mut group = TodoGroup::new("Inbox".to_string());
+        group.id = next_id(&mut data.id_counter);
+        page.add_group(group);
+    }
+    &mut page.group_list[0]
+}
+
+// Finishing a todo also spawns its next occurrence and completes parents waiting on it
+fn set_state(g: &mut TodoGroup, todo: usize, state: TodoState, reason: Option<String>,
+    now: NaiveDateTime, counter: &mut u32, unfinished: &BTreeSet<u32>) -> Option<()> {
+    let item = g.item_list.get_mut(todo)?;
+    let finished = state == TodoState::Done && item.state != TodoState::Done;
+    item.mark(state, now);
+    item.failed_reason = if state == TodoState::Failed { reason } else { None };
+    if !finished {
+        return Some(());
+    }
+
+    if let Some(mut next) = item.take_next_occurrence(now) {
+        next.id = next_id(counter);
+        let end = g.subtree_end(todo);
+        g.item_list.insert(end, next);
+    }
+    g.complete_parents(todo, now, unfinished);
+    Some(())
+}
+
+// Todos that aren't done, so anything they block is still waiting; taken before the change
+fn unfinished_ids(pages: &[TodoPage]) -> BTreeSet<u32> {
+    pages.iter().flat_map(|p| &p.group_list).flat_map(|g| &g.item_list)
+        .filter(|t| t.state != TodoState::Done)
+        .map(|t| t.id)
+        .collect()
+}
+
+fn group_mut(pages: &mut [TodoPage], p: usize, g: usize) -> Option<&mut TodoGroup> {
+    pages.get_mut(p)?.group_list.get_mut(g)
+}
+
+fn item_mut(pages: &mut [TodoPage], p: usize, g: usize, t: usize) -> Option<&mut TodoItem> {
+    group_mut(pages, p, g)?.item_list.get_mut(t)
+}
+
+fn search_name(data: &TodoData, s: usize) -> String {
+    data.searches.get(s).map_or("search".to_string(), |search| format!("search \"{}\"", search.title))
+}
+
+fn page_name(pages: &[TodoPage], p: usize) -> String {
+    pages.get(p).map_or("page".to_string(), |page| format!("page \"{}\"", page.title))
+}
+
+fn group_name(pages: &[TodoPage], p: usize, g: usize) -> String {
+    pages.get(p).and_then(|page| page.group_list.get(g))
+        .map_or("group".to_string(), |group| format!("group \"{}\"", group.title))
+}
+
+fn item_name(pages: &[TodoPage], p: usize, g: usize, t: usize) -> String {
+    pages.get(p).and_then(|page| page.group_list.get(g)).and_then(|group| group.item_list.get(t))
+        .map_or("todo".to_string(), |item| format!("\"{}\"", item.title))
+}
@@ -0,0 +1,216 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::{Priority, TodoItem, TodoState};
+
+// Furthest a due<Nd or due>Nd term may look ahead, about a hundred years
+const MAX_DAYS: i64 = 36500;
+
+// A query saved under a name; it shows up in the page list with live results
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub title: String,
+    pub query: String,
+}
+
+// Space separated terms that all have to hold, e.g.
+// `state:pending,doing tag:work due<7d -priority:none text:"deploy app"`.
+// due:today, due:overdue and due:none look at the due date alone.
+// A leading - negates a term; a bare word is the same as text:word.
+#[derive(Clone)]
+pub struct Query {
+    terms: Vec<(bool, Term)>, // (negated, term)
+}
+
+#[derive(Clone)]
+enum Term {
+    State(Vec<TodoState>),
+    Tag(String),
+    Priority(Vec<Option<Priority>>),
+    DueWithin(i64), // Days from now
+    DueAfter(i64),
+    DueToday,
+    Overdue, // Late, or unfinished and past due
+    NoDue,
+    Text(String),
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, String> {
+        let mut terms = Vec::new();
+        for word in split_words(text)? {
+            let (negated, word) = match word.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+                _ => (false, word),
+            };
+            terms.push((negated, parse_term(&word)?));
+        }
+        Ok(Query { terms })
+    }
+
+    pub fn matches(&self, item: &TodoItem, now: NaiveDateTime) -> bool {
+        self.terms.iter().all(|(negated, term)| term.matches(item, now) != *negated)
+    }
+}
+
+impl Term {
+    fn matches(&self, item: &TodoItem, now: NaiveDateTime) -> bool {
+        match self {
+            Term::State(states) => states.contains(&item.state),
+            Term::Tag(tag) => item.tags.contains(tag),
+            Term::Priority(priorities) => priorities.contains(&item.priority),
+            Term::DueWithin(days) => item.due.zip(horizon(now, *days)).is_some_and(|(d, h)| d.deadline() < h),
+            Term::DueAfter(days) => item.due.zip(horizon(now, *days)).is_some_and(|(d, h)| d.deadline() > h),
+            Term::DueToday => item.due.is_some_and(|d| d.date == now.date()),
+            Term::Overdue => item.state == TodoState::Late
+                || (!item.state.is_finished() && item.due.is_some_and(|d| d.is_past(now))),
+            Term::NoDue => item.due.is_none(),
+            Term::Text(text) => item.title.to_lowercase().contains(text) || item.description.to_lowercase().contains(text),
+        }
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term, String> {
+    if let Some(days) = word.strip_prefix("due<") {
+        return Ok(Term::DueWithin(parse_days(days)?));
+    }
+    if let Some(days) = word.strip_prefix("due>") {
+        return Ok(Term::DueAfter(parse_days(days)?));
+    }
+    let Some((key, value)) = word.split_once(':') else {
+        return Ok(Term::Text(word.to_lowercase()));
+    };
+
+    let value = value.to_lowercase();
+    match key.to_lowercase().as_str() {
+        "state" => value.split(',').map(|name| {
+            TodoState::ALL.into_iter().find(|s| s.label().to_lowercase() == name)
+                .ok_or(format!("Unknown state \"{}\"", name))
+        }).collect::<Result<_, _>>().map(Term::State),
+        "priority" => value.split(',').map(|name| match name {
+            "none" => Ok(None),
+            _ => [Priority::P1, Priority::P2, Priority::P3].into_iter().find(|p| p.label().to_lowercase() == name)
+                .map(Some).ok_or(format!("Unknown priority \"{}\", use p1, p2, p3 or none", name)),
+        }).collect::<Result<_, _>>().map(Term::Priority),
+        "tag" => Ok(Term::Tag(value.trim_start_matches('#').to_string())),
+        "due" => match value.as_str() {
+            "today" => Ok(Term::DueToday),
+            "overdue" => Ok(Term::Overdue),
+            "none" => Ok(Term::NoDue),
+            _ => Err(format!("Can't read \"{}\", use due:today, due:overdue or due:none", word)),
+        },
+        "text" => Ok(Term::Text(value)),
+        _ => Err(format!("Can't read \"{}\"", word)),
+    }
+}
+
+fn horizon(now: NaiveDateTime, days: i64) -> Option<NaiveDateTime> {
+    now.checked_add_signed(TimeDelta::try_days(days)?)
+}
+
+// "7d" or "2w" as days; "0d" is now
+fn parse_days(text: &str) -> Result<i64, String> {
+    let count = text.trim_end_matches(|c: char| c.is_alphabetic());
+    let unit = &text[count.len()..];
+    let count: i64 = count.parse().map_err(|_| format!("Can't read \"{}\", use Nd or Nw", text))?;
+    let days = match unit {
+        "d" | "" => count,
+        "w" => count.saturating_mul(7),
+        _ => return Err(format!("Can't read \"{}\", use Nd or Nw", text)),
+    };
+    if !(0..=MAX_DAYS).contains(&days) {
+        return Err(format!("\"{}\" is out of range, use up to {} days", text, MAX_DAYS));
+    }
+    Ok(days)
+}
+
+// Splits on spaces, except inside double quotes, which are dropped
+fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if quoted {
+        return Err("Missing closing quote".to_string());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::due::Due;
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2025-06-01 12:00", "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn item(title: &str, due: Option<&str>) -> TodoItem {
+        let mut item = TodoItem::new(title.to_string());
+        item.due = due.map(|d| Due { date: NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap(), time: None });
+        item
+    }
+
+    fn matches(query: &str, item: &TodoItem) -> bool {
+        Query::parse(query).unwrap().matches(item, now())
+    }
+
+    #[test]
+    fn terms_all_have_to_hold() {
+        let mut todo = item("Deploy the app", Some("2025-06-03"));
+        todo.tags.insert("work".to_string());
+        todo.priority = Some(Priority::P1);
+
+        assert!(matches("tag:work due<7d priority:p1,p2 deploy", &todo));
+        assert!(matches("state:pending,doing text:\"the app\"", &todo));
+        assert!(!matches("tag:work -priority:p1", &todo));
+        assert!(!matches("due>7d", &todo));
+        assert!(matches("-due:none", &todo));
+    }
+
+    #[test]
+    fn due_today_and_overdue() {
+        assert!(matches("due:today", &item("a", Some("2025-06-01"))));
+        assert!(!matches("due:overdue", &item("a", Some("2025-06-01"))));
+        assert!(matches("due:overdue", &item("a", Some("2025-05-31"))));
+
+        let mut done = item("a", Some("2025-05-31"));
+        done.state = TodoState::Done;
+        assert!(!matches("due:overdue", &done));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Query::parse("state:sleeping").is_err());
+        assert!(Query::parse("priority:p9").is_err());
+        assert!(Query::parse("due:later").is_err());
+        assert!(Query::parse("colour:red").is_err());
+        assert!(Query::parse("due<soon").is_err());
+        assert!(Query::parse("text:\"unclosed").is_err());
+    }
+
+    #[test]
+    fn day_counts_are_bounded() {
+        assert!(Query::parse("due<36500d").is_ok());
+        assert!(Query::parse("due<36501d").is_err());
+        assert!(Query::parse("due<100000000d").is_err());
+        assert!(Query::parse("due>99999999999999w").is_err());
+        assert!(Query::parse("due<-3d").is_err());
+        assert!(!matches("due>36500d", &item("a", Some("2025-06-03"))));
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::due::Due;
+
+// Longest gap a repeat rule accepts, about ten years
+const MAX_DAYS: u32 = 3650;
+
+// How a todo comes back once it's done
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum Recurrence {
+    Daily,
+    Weekly(Vec<Weekday>), // Empty means the same weekday as the due date
+    Monthly, // Same day of the month as the due date; pinned to MonthlyOn once it repeats
+    MonthlyOn(u32), // Day of the month, or the month's last day when it's shorter
+    EveryDays(u32),
+    AfterCompletion(u32), // Days counted from when it was completed, not from the due date
+}
+
+impl Recurrence {
+    // Accepts "daily", "weekly", "weekly mon,thu", "monthly", "monthly 31", "every 3d" and "after 3d".
+    // Empty text means the todo doesn't repeat.
+    pub fn parse(text: &str) -> Result<Option<Recurrence>, String> {
+        let text = text.trim().to_lowercase();
+        let mut words = text.split_whitespace();
+        let Some(kind) = words.next() else {
+            return Ok(None);
+        };
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err("Too much text for a repeat rule".to_string());
+        }
+
+        let rule = match (kind, argument) {
+            ("daily", None) => Recurrence::Daily,
+            ("monthly", None) => Recurrence::Monthly,
+            ("monthly", Some(day)) => match day.parse() {
+                Ok(day) if (1..=31).contains(&day) => Recurrence::MonthlyOn(day),
+                _ => return Err(format!("Can't read day \"{}\", use 1 to 31", day)),
+            },
+            ("weekly", None) => Recurrence::Weekly(Vec::new()),
+            ("weekly", Some(days)) => {
+                let mut weekdays = Vec::new();
+                for day in days.split(',').filter(|d| !d.is_empty()) {
+                    let weekday: Weekday = day.parse().map_err(|_| format!("Can't read weekday \"{}\"", day))?;
+                    if !weekdays.contains(&weekday) {
+                        weekdays.push(weekday);
+                    }
+                }
+                weekdays.sort_by_key(|d| d.num_days_from_monday());
+                Recurrence::Weekly(weekdays)
+            }
+            ("every", Some(days)) => Recurrence::EveryDays(parse_days(days)?),
+            ("after", Some(days)) => Recurrence::AfterCompletion(parse_days(days)?),
+            _ => return Err("Use daily, weekly [mon,thu], monthly [day], every Nd or after Nd".to_string()),
+        };
+        Ok(Some(rule))
+    }
+
+    // The rule the next occurrence carries: a plain monthly rule remembers the due date's
+    // day, so a 31st that had to fall on the 28th goes back to the 31st the month after
+    pub fn pinned(self, due: Option<Due>) -> Recurrence {
+        match (self, due) {
+            (Recurrence::Monthly, Some(due)) => Recurrence::MonthlyOn(due.date.day()),
+            (rule, _) => rule,
+        }
+    }
+
+    // Due date of the occurrence after one with `due`, completed `today`; None once
+    // the dates run out
+    pub fn next_due(&self, due: Option<Due>, today: NaiveDate) -> Option<Due> {
+        let time = due.and_then(|d| d.time);
+        if let Recurrence::AfterCompletion(days) = self {
+            return Some(Due { date: add_days(today, *days as u64)?, time });
+        }
+
+        // Fixed schedules skip the dates that already went by
+        let rule = self.clone().pinned(due);
+        let mut date = due.map_or(today, |d| d.date);
+        loop {
+            date = rule.step(date)?;
+            if date >= today {
+                return Some(Due { date, time });
+            }
+        }
+    }
+
+    // Always moves forward, or gives None
+    fn step(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Daily => add_days(date, 1),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => add_days(date, 7),
+            Recurrence::Weekly(weekdays) => {
+                (1..=7).filter_map(|days| add_days(date, days))
+                    .find(|next| weekdays.contains(&next.weekday()))
+            }
+            Recurrence::Monthly => date.checked_add_months(Months::new(1)),
+            Recurrence::MonthlyOn(day) => {
+                let month = date.with_day(1)?.checked_add_months(Months::new(1))?;
+                (1..=*day).rev().find_map(|d| month.with_day(d))
+            }
+            Recurrence::EveryDays(days) | Recurrence::AfterCompletion(days) => add_days(date, *days as u64),
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => write!(f, "weekly"),
+            Recurrence::Weekly(weekdays) => {
+                let days: Vec<String> = weekdays.iter().map(|d| d.to_string().to_lowercase()).collect();
+                write!(f, "weekly {}", days.join(","))
+            }
+            Recurrence::Monthly => write!(f, "monthly"),
+            Recurrence::MonthlyOn(day) => write!(f, "monthly {}", day),
+            Recurrence::EveryDays(days) => write!(f, "every {}d", days),
+            Recurrence::AfterCompletion(days) => write!(f, "after {}d", days),
+        }
+    }
+}
+
+fn parse_days(text: &str) -> Result<u32, String> {
+    match text.trim_end_matches('d').parse() {
+        Ok(days) if days > 0 && days <= MAX_DAYS => Ok(days),
+        _ => Err(format!("Can't read \"{}\", use 1d to {}d", text, MAX_DAYS)),
+    }
+}
+
+fn add_days(date: NaiveDate, days: u64) -> Option<NaiveDate> {
+    date.checked_add_days(Days::new(days))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn due(text: &str) -> Option<Due> {
+        Some(Due { date: date(text), time: None })
+    }
+
+    #[test]
+    fn weekly_with_weekdays_takes_the_next_listed_day() {
+        let rule = Recurrence::parse("weekly mon,thu").unwrap().unwrap();
+        // 2025-06-02 is a Monday
+        assert_eq!(rule.next_due(due("2025-06-02"), date("2025-06-02")).unwrap().date, date("2025-06-05"));
+        assert_eq!(rule.next_due(due("2025-06-05"), date("2025-06-05")).unwrap().date, date("2025-06-09"));
+    }
+
+    #[test]
+    fn weekly_skips_weeks_that_already_went_by() {
+        let rule = Recurrence::parse("weekly").unwrap().unwrap();
+        assert_eq!(rule.next_due(due("2025-06-02"), date("2025-06-20")).unwrap().date, date("2025-06-23"));
+    }
+
+    #[test]
+    fn monthly_clamps_to_the_end_of_a_shorter_month() {
+        let rule = Recurrence::parse("monthly").unwrap().unwrap();
+        assert_eq!(rule.next_due(due("2025-01-31"), date("2025-01-31")).unwrap().date, date("2025-02-28"));
+        assert_eq!(rule.next_due(due("2024-01-31"), date("2024-01-31")).unwrap().date, date("2024-02-29"));
+    }
+
+    #[test]
+    fn monthly_goes_back_to_the_anchor_day() {
+        let rule = Recurrence::parse("monthly").unwrap().unwrap();
+        let feb = rule.next_due(due("2025-01-31"), date("2025-01-31")).unwrap();
+        let rule = rule.pinned(due("2025-01-31"));
+        let mar = rule.next_due(Some(feb), date("2025-02-28")).unwrap();
+        let apr = rule.next_due(Some(mar), date("2025-03-31")).unwrap();
+        assert_eq!([feb.date, mar.date, apr.date], [date("2025-02-28"), date("2025-03-31"), date("2025-04-30")]);
+        assert_eq!(rule.to_string(), "monthly 31");
+    }
+
+    #[test]
+    fn runs_out_instead_of_looping_at_the_end_of_time() {
+        let rule = Recurrence::EveryDays(MAX_DAYS);
+        assert!(rule.next_due(Some(Due { date: NaiveDate::MAX, time: None }), date("2025-06-01")).is_none());
+        assert!(rule.next_due(due("2020-01-01"), NaiveDate::MAX).is_none());
+    }
+
+    #[test]
+    fn after_completion_counts_from_today_and_keeps_the_time() {
+        let rule = Recurrence::parse("after 3d").unwrap().unwrap();
+        let time = chrono::NaiveTime::from_hms_opt(9, 30, 0);
+        let next = rule.next_due(Some(Due { date: date("2025-06-01"), time }), date("2025-06-10")).unwrap();
+        assert_eq!(next.date, date("2025-06-13"));
+        assert_eq!(next.time, time);
+    }
+
+    #[test]
+    fn parse_rejects_bad_rules() {
+        assert!(Recurrence::parse("weekly funday").is_err());
+        assert!(Recurrence::parse("every 0d").is_err());
+        assert!(Recurrence::parse("every 4000000000d").is_err());
+        assert!(Recurrence::parse("monthly 32").is_err());
+        assert!(Recurrence::parse("yearly").is_err());
+        assert!(Recurrence::parse("").unwrap().is_none());
+    }
+}
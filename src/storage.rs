@@ -0,0 +1,310 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+use chrono::NaiveDateTime;
+use color_eyre::Result;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{TodoData, TodoPage, mutation::{Entry, Mutation}};
+
+const APP_DIR: &str = "todo_rs";
+const DATA_FILE: &str = "todos.json";
+const JOURNAL_FILE: &str = "journal.jsonl";
+const RECOVERY_FILE: &str = "recovery.json";
+
+// Journal entries allowed to pile up before they get folded into a snapshot
+const SNAPSHOT_EVERY: usize = 50;
+
+// $TODO_RS_DATA_DIR overrides the XDG data directory (handy for testing)
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("TODO_RS_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_DIR)
+}
+
+// What came back from disk, and how much of it had to be recovered
+pub struct Loaded {
+    pub data: TodoData,
+    pub replayed: usize,
+    pub discarded: usize,
+    pub save_error: Option<String>, // The data loaded fine but couldn't be written back
+}
+
+// Snapshot file (written via temp file + rename) plus an append-only journal
+// of every mutation since that snapshot. Entries carry a sequence number and the
+// snapshot the last one it covers, so a journal left over from a crash between
+// the two writes isn't applied a second time.
+pub struct Storage {
+    dir: PathBuf,
+    journal: Option<File>,
+    pending: usize,
+    seq: u64, // Last sequence number handed out
+}
+
+impl Storage {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            journal: None,
+            pending: 0,
+            seq: 0,
+        }
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.dir.join(DATA_FILE)
+    }
+
+    fn journal_path(&self) -> PathBuf {
+        self.dir.join(JOURNAL_FILE)
+    }
+
+    fn recovery_path(&self) -> PathBuf {
+        self.dir.join(RECOVERY_FILE)
+    }
+
+    pub fn load(&mut self) -> Result<Loaded> {
+        let snapshot_path = self.snapshot_path();
+        let (mut data, covered) = if snapshot_path.exists() {
+            let contents = fs::read_to_string(&snapshot_path)?;
+            (parse_data(&contents)?, covered_seq(&contents))
+        }
+        else { // First launch
+            (TodoData::default(), 0)
+        };
+        self.seq = covered;
+
+        let migrated = data.assign_missing_ids();
+
+        let mut replayed = 0;
+        let mut discarded = 0;
+        let mut stale = false;
+        if let Ok(file) = File::open(self.journal_path()) {
+            let mut lines = BufReader::new(file).lines();
+            while let Some(line) = lines.next() {
+                let entry = line.ok().and_then(|l| serde_json::from_str::<Entry>(&l).ok());
+                if let Some(entry) = &entry && entry.seq != 0 && entry.seq <= covered {
+                    stale = true; // Already in the snapshot
+                    continue;
+                }
+
+                // A torn last line (killed mid-append) or a mutation that no
+                // longer fits ends the replay; everything after it is dropped
+                let applied = entry.and_then(|entry| {
+                    self.seq = self.seq.max(entry.seq);
+                    entry.mutation.apply(&mut data, entry.at)
+                });
+                if applied.is_none() {
+                    discarded = 1 + lines.count();
+                    break;
+                }
+                replayed += 1;
+            }
+        }
+
+        // Fold whatever was recovered into a fresh snapshot
+        let mut save_error = None;
+        if migrated || stale || replayed > 0 || discarded > 0 {
+            save_error = self.snapshot(&data).err().map(|e| e.to_string());
+        }
+
+        Ok(Loaded { data, replayed, discarded, save_error })
+    }
+
+    pub fn record(&mut self, mutation: Mutation, at: NaiveDateTime, data: &TodoData) -> Result<()> {
+        if self.journal.is_none() {
+            fs::create_dir_all(&self.dir)?;
+            self.journal = Some(OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.journal_path())?);
+        }
+
+        let journal = self.journal.as_mut().unwrap();
+        self.seq += 1;
+        let mut line = serde_json::to_string(&Entry { seq: self.seq, at, mutation })?;
+        line.push('\n');
+        journal.write_all(line.as_bytes())?;
+        journal.sync_data()?;
+
+        self.pending += 1;
+        if self.pending >= SNAPSHOT_EVERY {
+            self.snapshot(data)?;
+        }
+        Ok(())
+    }
+
+    pub fn snapshot(&mut self, data: &TodoData) -> Result<()> {
+        let file = SnapshotFile { data, journal_seq: self.seq };
+        write_atomic(&self.snapshot_path(), &serde_json::to_string_pretty(&file)?)?;
+
+        // Snapshot now covers everything in the journal
+        self.journal = None;
+        File::create(self.journal_path())?.sync_all()?;
+        self.pending = 0;
+        Ok(())
+    }
+
+    // Emergency dump of whatever was in memory when the app panicked
+    pub fn write_recovery(&self, data: &TodoData) -> Result<PathBuf> {
+        let path = self.recovery_path();
+        write_atomic(&path, &serde_json::to_string_pretty(data)?)?;
+        Ok(path)
+    }
+
+    pub fn read_recovery(&self) -> Result<Option<TodoData>> {
+        let path = self.recovery_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(parse_data(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn clear_recovery(&self) -> Result<()> {
+        fs::remove_file(self.recovery_path())?;
+        Ok(())
+    }
+
+    // Moves unreadable data out of the way so the next save can't clobber it
+    pub fn quarantine(&self) -> Result<PathBuf> {
+        let backup = self.snapshot_path().with_extension("json.corrupt");
+        fs::rename(self.snapshot_path(), &backup)?;
+        let _ = fs::rename(self.journal_path(), self.journal_path().with_extension("jsonl.corrupt"));
+        Ok(backup)
+    }
+}
+
+#[derive(Serialize)]
+struct SnapshotFile<'a> {
+    #[serde(flatten)]
+    data: &'a TodoData,
+    journal_seq: u64,
+}
+
+// The last journal entry a snapshot includes; 0 for snapshots older than sequence numbers
+fn covered_seq(contents: &str) -> u64 {
+    #[derive(Deserialize)]
+    struct Covered {
+        #[serde(default)]
+        journal_seq: u64,
+    }
+    serde_json::from_str::<Covered>(contents).map_or(0, |c| c.journal_seq)
+}
+
+// Files written before the trash existed hold just the page list
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DataFile {
+    Current(TodoData),
+    PagesOnly(Vec<TodoPage>),
+}
+
+fn parse_data(contents: &str) -> Result<TodoData> {
+    Ok(match serde_json::from_str(contents)? {
+        DataFile::Current(data) => data,
+        DataFile::PagesOnly(page_list) => TodoData { page_list, ..TodoData::default() },
+    })
+}
+
+// Temp file + fsync + rename, so readers only ever see the old or the new file
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    if let Ok(dir) = File::open(dir) { // Persist the rename itself
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("todo_rs_{}_{}", name, std::process::id()))
+    }
+
+    fn fresh_storage(name: &str) -> Storage {
+        let _ = fs::remove_dir_all(temp_dir(name));
+        Storage::new(temp_dir(name))
+    }
+
+    fn at() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2025-06-01 12:00", "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    // Applies and journals a page, like ApplicationState::commit does
+    fn add_page(storage: &mut Storage, data: &mut TodoData, title: &str) {
+        let mutation = Mutation::AddPage { title: title.to_string() };
+        mutation.apply(data, at()).unwrap();
+        storage.record(mutation, at(), data).unwrap();
+    }
+
+    fn titles(data: &TodoData) -> Vec<&str> {
+        data.page_list.iter().map(|p| p.title.as_str()).collect()
+    }
+
+    #[test]
+    fn replay_stops_at_a_torn_line() {
+        let mut storage = fresh_storage("torn");
+        let mut data = TodoData::default();
+        add_page(&mut storage, &mut data, "A");
+        add_page(&mut storage, &mut data, "B");
+        let mut journal = OpenOptions::new().append(true).open(storage.journal_path()).unwrap();
+        journal.write_all(br#"{"seq":3,"at":"2025-06-01T12:00:00","mutation":{"AddPa"#).unwrap();
+
+        let loaded = Storage::new(temp_dir("torn")).load().unwrap();
+        assert_eq!(titles(&loaded.data), ["A", "B"]);
+        assert_eq!(loaded.replayed, 2);
+        assert_eq!(loaded.discarded, 1);
+        assert!(loaded.save_error.is_none());
+
+        // The replay got folded into the snapshot, so a second load has nothing to recover
+        let again = Storage::new(temp_dir("torn")).load().unwrap();
+        assert_eq!(titles(&again.data), ["A", "B"]);
+        assert_eq!(again.replayed, 0);
+        fs::remove_dir_all(&storage.dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_covers_a_journal_left_behind() {
+        let mut storage = fresh_storage("stale");
+        let mut data = TodoData::default();
+        add_page(&mut storage, &mut data, "A");
+        add_page(&mut storage, &mut data, "B");
+        let journal = fs::read(storage.journal_path()).unwrap();
+
+        // Killed after the snapshot landed but before the journal was emptied
+        storage.snapshot(&data).unwrap();
+        fs::write(storage.journal_path(), journal).unwrap();
+
+        let mut reopened = Storage::new(temp_dir("stale"));
+        let loaded = reopened.load().unwrap();
+        assert_eq!(titles(&loaded.data), ["A", "B"]);
+        assert_eq!(loaded.replayed, 0);
+
+        // Later entries still get replayed on top
+        let mut data = loaded.data;
+        add_page(&mut reopened, &mut data, "C");
+        let loaded = Storage::new(temp_dir("stale")).load().unwrap();
+        assert_eq!(titles(&loaded.data), ["A", "B", "C"]);
+        assert_eq!(loaded.replayed, 1);
+        fs::remove_dir_all(&storage.dir).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{TodoGroup, TodoItem, TodoPage};
+
+// Something deleted, along with enough of its origin to put it back
+#[derive(Clone, Serialize, Deserialize)]
+pub enum TrashEntry {
+    Page { index: usize, page: TodoPage },
+    Group { page: usize, page_title: String, index: usize, group: TodoGroup },
+    Todo {
+        page: usize,
+        page_title: String,
+        group: usize,
+        group_title: String,
+        index: usize,
+        item: Box<TodoItem>,
+        #[serde(default)]
+        children: Vec<TodoItem>, // Subtasks deleted along with it
+    },
+}
+
+impl TrashEntry {
+    pub fn label(&self) -> String {
+        match self {
+            TrashEntry::Page { page, .. } => format!("Page: {}", page.title),
+            TrashEntry::Group { page_title, group, .. } => format!("Group: {} ({})", group.title, page_title),
+            TrashEntry::Todo { page_title, group_title, item, children, .. } if children.is_empty() => {
+                format!("Todo: {} ({} › {})", item.title, page_title, group_title)
+            }
+            TrashEntry::Todo { page_title, group_title, item, children, .. } => {
+                format!("Todo: {} +{} subtask(s) ({} › {})", item.title, children.len(), page_title, group_title)
+            }
+        }
+    }
+
+    // Where the entry would go back to; None once its parent is gone too
+    fn destination(&self, pages: &[TodoPage]) -> Option<(usize, usize)> {
+        match self {
+            TrashEntry::Page { .. } => Some((0, 0)),
+            TrashEntry::Group { page, page_title, .. } => Some((find_page(pages, *page, page_title)?, 0)),
+            TrashEntry::Todo { page, page_title, group, group_title, .. } => {
+                let p = find_page(pages, *page, page_title)?;
+                let groups = &pages[p].group_list;
+                let g = if groups.get(*group).is_some_and(|g| &g.title == group_title) {
+                    *group
+                }
+                else {
+                    groups.iter().position(|g| &g.title == group_title)?
+                };
+                Some((p, g))
+            }
+        }
+    }
+
+    pub fn can_restore(&self, pages: &[TodoPage]) -> bool {
+        self.destination(pages).is_some()
+    }
+
+    // Puts the entry back at its old position (or the end, if things shifted)
+    pub fn restore(self, pages: &mut Vec<TodoPage>) -> Option<()> {
+        let (p, g) = self.destination(pages)?;
+        match self {
+            TrashEntry::Page { index, page } => {
+                pages.insert(index.min(pages.len()), page);
+            }
+            TrashEntry::Group { index, group, .. } => {
+                let groups = &mut pages[p].group_list;
+                groups.insert(index.min(groups.len()), group);
+            }
+            TrashEntry::Todo { index, item, children, .. } => {
+                let depth = item.depth;
+                let mut subtree = vec![*item];
+                subtree.extend(children);
+                pages[p].group_list[g].insert_subtree(index, subtree, depth);
+            }
+        }
+        Some(())
+    }
+}
+
+// Prefer the recorded index, but follow the page by title if it moved
+fn find_page(pages: &[TodoPage], index: usize, title: &str) -> Option<usize> {
+    if pages.get(index).is_some_and(|p| p.title == title) {
+        return Some(index);
+    }
+    pages.iter().position(|p| p.title == title)
+}